@@ -4,6 +4,15 @@ set -e
 cd ./defi-borrow
 ./build.sh
 
+cd ../mock-metapool
+./build.sh
+
+cd ../mock-wnear
+./build.sh
+
+cd ../mock-dex
+./build.sh
+
 cd ../draw
 ./build.sh
 
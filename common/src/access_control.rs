@@ -0,0 +1,319 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::UnorderedMap;
+use near_sdk::serde::Serialize;
+use near_sdk::{env, AccountId, IntoStorageKey};
+use std::fmt::Debug;
+
+/// The roles of a contract, the admins manage the other roles
+pub trait ContractRole: BorshSerialize + BorshDeserialize + Serialize + Copy + PartialEq + Debug{
+    fn admin() -> Self;
+}
+
+/// The owner has every role. Ownership is transferred in two steps,
+/// the new owner has to accept it
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct AccessControl<R>{
+    pub owner_id: AccountId,
+    pub pending_owner_id: Option<AccountId>,
+    pub roles: UnorderedMap<AccountId, Vec<R>>,
+}
+
+impl<R: ContractRole> AccessControl<R>{
+    pub fn new<S: IntoStorageKey>(owner_id: AccountId, roles_prefix: S) -> Self{
+        Self {
+            owner_id: owner_id,
+            pending_owner_id: None,
+            roles: UnorderedMap::new(roles_prefix),
+        }
+    }
+
+    pub fn get_roles(&self, account_id: &AccountId) -> Vec<R>{
+        return self.roles.get(&account_id).unwrap_or_default();
+    }
+
+    pub fn has_role(&self, role: R, account_id: &AccountId) -> bool{
+        return account_id == &self.owner_id || self.get_roles(account_id).contains(&role);
+    }
+
+    pub fn grant_role(&mut self, role: R, account_id: &AccountId) -> bool{
+        let mut roles = self.get_roles(account_id);
+        if roles.contains(&role){
+            return false;
+        }
+
+        roles.push(role);
+        self.roles.insert(&account_id, &roles);
+        return true;
+    }
+
+    pub fn revoke_role(&mut self, role: R, account_id: &AccountId) -> bool{
+        let mut roles = self.get_roles(account_id);
+        if !roles.contains(&role){
+            return false;
+        }
+
+        roles.retain(|el| *el != role);
+        if roles.is_empty(){
+            self.roles.remove(&account_id);
+        } else {
+            self.roles.insert(&account_id, &roles);
+        }
+        return true;
+    }
+
+    pub fn assert_owner(&self){
+        assert_eq!(env::predecessor_account_id(), self.owner_id, "Only the owner can call this method");
+    }
+
+    pub fn assert_role(&self, role: R){
+        assert!(self.has_role(role, &env::predecessor_account_id()), "Caller doesn't have the {:?} role", role);
+    }
+
+    /// Only the owner can manage admins, admins can manage the other roles
+    pub fn assert_can_manage_role(&self, role: R){
+        if role == R::admin(){
+            self.assert_owner();
+        } else {
+            self.assert_role(R::admin());
+        }
+    }
+
+    /// Makes the caller the owner if it is the pending owner, returns the old owner
+    pub fn accept_ownership(&mut self) -> AccountId{
+        let caller = env::predecessor_account_id();
+        assert_eq!(Some(caller.clone()), self.pending_owner_id, "Only the pending owner can accept the ownership");
+
+        self.pending_owner_id = None;
+        return std::mem::replace(&mut self.owner_id, caller);
+    }
+}
+
+pub mod events{
+    use near_sdk::{AccountId, log};
+    use near_sdk::serde::{Serialize};
+    use near_sdk::serde_json::json;
+
+    #[derive(Serialize)]
+    #[serde(crate = "near_sdk::serde")]
+    struct OwnershipEvent<'a> {
+        pub old_owner_id: &'a AccountId,
+        pub new_owner_id: &'a AccountId,
+    }
+
+    #[derive(Serialize)]
+    #[serde(crate = "near_sdk::serde")]
+    struct RoleEvent<'a, R: Serialize> {
+        pub account_id: &'a AccountId,
+        pub role: R,
+        pub sender_id: &'a AccountId,
+    }
+
+    fn log_event<T: Serialize>(standard: &str, event: &str, data: T) {
+        let event = json!({
+            "standard": standard,
+            "version": "1.0.0",
+            "event": event,
+            "data": [data]
+        });
+
+        log!("EVENT_JSON:{}", event.to_string());
+    }
+
+    pub fn ownership_transfer_started(standard: &str, old_owner_id: &AccountId, new_owner_id: &AccountId){
+        log_event(
+            standard,
+            "ownership_transfer_started",
+            OwnershipEvent {
+                old_owner_id: &old_owner_id,
+                new_owner_id: &new_owner_id,
+            }
+        );
+    }
+
+    pub fn ownership_transferred(standard: &str, old_owner_id: &AccountId, new_owner_id: &AccountId){
+        log_event(
+            standard,
+            "ownership_transferred",
+            OwnershipEvent {
+                old_owner_id: &old_owner_id,
+                new_owner_id: &new_owner_id,
+            }
+        );
+    }
+
+    pub fn role_granted<R: Serialize>(standard: &str, account_id: &AccountId, role: R, sender_id: &AccountId){
+        log_event(
+            standard,
+            "role_granted",
+            RoleEvent {
+                account_id: &account_id,
+                role: role,
+                sender_id: &sender_id,
+            }
+        );
+    }
+
+    pub fn role_revoked<R: Serialize>(standard: &str, account_id: &AccountId, role: R, sender_id: &AccountId){
+        log_event(
+            standard,
+            "role_revoked",
+            RoleEvent {
+                account_id: &account_id,
+                role: role,
+                sender_id: &sender_id,
+            }
+        );
+    }
+}
+
+/// Adds the owner and role methods of the `AccessControl` field to the contract.
+/// The events are logged with the event standard of the contract
+#[macro_export]
+macro_rules! impl_access_control {
+    ($contract: ident, $access_control: ident, $role: ident, $standard: expr) => {
+        impl $contract{
+            pub (crate) fn assert_role(&self, role: $role){
+                self.$access_control.assert_role(role);
+            }
+        }
+
+        #[near_sdk::near_bindgen]
+        impl $contract{
+            pub fn get_owner(&self) -> near_sdk::AccountId{
+                return self.$access_control.owner_id.clone();
+            }
+
+            pub fn get_pending_owner(&self) -> Option<near_sdk::AccountId>{
+                return self.$access_control.pending_owner_id.clone();
+            }
+
+            pub fn get_roles(&self, account_id: near_sdk::AccountId) -> Vec<$role>{
+                return self.$access_control.get_roles(&account_id);
+            }
+
+            pub fn has_role(&self, role: $role, account_id: near_sdk::AccountId) -> bool{
+                return self.$access_control.has_role(role, &account_id);
+            }
+
+            #[payable]
+            pub fn grant_role(&mut self, role: $role, account_id: near_sdk::AccountId){
+                near_sdk::assert_one_yocto();
+                self.$access_control.assert_can_manage_role(role);
+
+                if self.$access_control.grant_role(role, &account_id){
+                    $crate::access_control::events::role_granted($standard, &account_id, role, &near_sdk::env::predecessor_account_id());
+                }
+            }
+
+            #[payable]
+            pub fn revoke_role(&mut self, role: $role, account_id: near_sdk::AccountId){
+                near_sdk::assert_one_yocto();
+                self.$access_control.assert_can_manage_role(role);
+
+                if self.$access_control.revoke_role(role, &account_id){
+                    $crate::access_control::events::role_revoked($standard, &account_id, role, &near_sdk::env::predecessor_account_id());
+                }
+            }
+
+            /// First step of the ownership transfer, `new_owner_id` has to accept the ownership
+            #[payable]
+            pub fn transfer_ownership(&mut self, new_owner_id: near_sdk::AccountId){
+                near_sdk::assert_one_yocto();
+                self.$access_control.assert_owner();
+
+                self.$access_control.pending_owner_id = Some(new_owner_id.clone());
+                $crate::access_control::events::ownership_transfer_started($standard, &self.$access_control.owner_id, &new_owner_id);
+            }
+
+            #[payable]
+            pub fn accept_ownership(&mut self){
+                near_sdk::assert_one_yocto();
+
+                let old_owner_id = self.$access_control.accept_ownership();
+                $crate::access_control::events::ownership_transferred($standard, &old_owner_id, &self.$access_control.owner_id);
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests{
+    use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+    use near_sdk::serde::Serialize;
+    use near_sdk::AccountId;
+
+    use super::{AccessControl, ContractRole};
+    use crate::test_utils::set_caller;
+
+    #[derive(BorshDeserialize, BorshSerialize, Serialize, Clone, Copy, PartialEq, Debug)]
+    #[serde(crate = "near_sdk::serde")]
+    enum Role{
+        Admin,
+        Keeper,
+    }
+
+    impl ContractRole for Role{
+        fn admin() -> Self{
+            return Role::Admin;
+        }
+    }
+
+    fn account(name: &str) -> AccountId{
+        AccountId::new_unchecked(name.to_string())
+    }
+
+    fn setup() -> AccessControl<Role>{
+        set_caller(account("owner.near"));
+        return AccessControl::new(account("owner.near"), b"r".to_vec());
+    }
+
+    #[test]
+    fn test_roles(){
+        let mut access_control = setup();
+        assert!(access_control.has_role(Role::Keeper, &account("owner.near")));
+        assert!(!access_control.has_role(Role::Keeper, &account("alice.near")));
+
+        access_control.assert_can_manage_role(Role::Admin);
+        assert!(access_control.grant_role(Role::Admin, &account("alice.near")));
+        set_caller(account("alice.near"));
+        access_control.assert_can_manage_role(Role::Keeper);
+        assert!(access_control.grant_role(Role::Keeper, &account("bob.near")));
+        assert!(!access_control.grant_role(Role::Keeper, &account("bob.near")));
+        assert_eq!(access_control.get_roles(&account("bob.near")), vec![Role::Keeper]);
+        assert!(!access_control.has_role(Role::Admin, &account("bob.near")));
+
+        assert!(access_control.revoke_role(Role::Keeper, &account("bob.near")));
+        assert!(access_control.get_roles(&account("bob.near")).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner can call this method")]
+    fn test_admin_cannot_manage_admins(){
+        let mut access_control = setup();
+        access_control.grant_role(Role::Admin, &account("alice.near"));
+
+        set_caller(account("alice.near"));
+        access_control.assert_can_manage_role(Role::Admin);
+    }
+
+    #[test]
+    fn test_two_step_ownership_transfer(){
+        let mut access_control = setup();
+        access_control.pending_owner_id = Some(account("alice.near"));
+
+        set_caller(account("alice.near"));
+        assert_eq!(access_control.accept_ownership(), account("owner.near"));
+        assert_eq!(access_control.owner_id, account("alice.near"));
+        assert_eq!(access_control.pending_owner_id, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the pending owner can accept the ownership")]
+    fn test_only_pending_owner_accepts_ownership(){
+        let mut access_control = setup();
+        access_control.pending_owner_id = Some(account("alice.near"));
+
+        set_caller(account("bob.near"));
+        access_control.accept_ownership();
+    }
+}
@@ -27,6 +27,11 @@ pub mod generic_ring_buffer{
         pub fn new() -> Self{
             Self::default()
         }
+
+        /// Converts every element, the next element is added at the same index
+        pub fn map<U, F: Fn(T) -> U>(&self, f: F) -> GenericRingBuffer<U, CAPACITY>{
+            return GenericRingBuffer { arr: self.arr.map(f), current_index: self.current_index };
+        }
     }
 
     impl<T: Copy, const CAPACITY: usize> RingBuffer<T> for GenericRingBuffer<T, CAPACITY>{
@@ -48,6 +53,9 @@ pub mod generic_ring_buffer{
 }
 
 pub mod types;
+pub mod access_control;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod test_utils;
 
 #[cfg(test)]
 mod tests {
@@ -80,4 +88,15 @@ mod tests {
         buffer.add(&current_element);
         assert!(buffer.get(1) == 5);
     }
+
+    #[test]
+    fn test_map_ring_buffer() {
+        let mut buffer = GenericRingBuffer::<u32, 2>::new();
+        buffer.add(&1);
+        let mut mapped = buffer.map(|el| u64::from(el) * 10);
+        assert!(mapped.get(0) == 10);
+
+        mapped.add(&20);
+        assert!(mapped.get(1) == 20);
+    }
 }
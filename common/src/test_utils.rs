@@ -0,0 +1,10 @@
+use near_sdk::test_utils::VMContextBuilder;
+use near_sdk::{testing_env, AccountId};
+
+/// Makes `account_id` the caller, with the one yocto the owner and admin methods require
+pub fn set_caller(account_id: AccountId){
+    testing_env!(VMContextBuilder::new()
+        .predecessor_account_id(account_id)
+        .attached_deposit(1)
+        .build());
+}
@@ -0,0 +1,112 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Serialize, Deserialize};
+use near_sdk::json_types::U128;
+use near_sdk::Balance;
+use common::types::U256;
+
+use crate::NumShares;
+
+pub const YEAR_NS: u64 = 365 * 24 * 60 * 60 * 1_000_000_000;
+pub const BPS_NOMINAL: u128 = 10_000;
+pub const DEFAULT_SUPPLY_APR_BPS: u32 = 500;
+
+fn mul_div(a: u128, b: u128, c: u128) -> u128{
+    return (U256::from(a) * U256::from(b) / U256::from(c)).as_u128();
+}
+
+fn mul_div_ceil(a: u128, b: u128, c: u128) -> u128{
+    return ((U256::from(a) * U256::from(b) + U256::from(c) - 1) / U256::from(c)).as_u128();
+}
+
+/// The tokens supplied by all accounts. The interest is added to the balance,
+/// so the price of a share grows with the time
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct AssetPool{
+    pub shares: NumShares,
+    /// The supplied tokens with the interest until `last_update`
+    pub balance: Balance,
+    pub supply_apr_bps: u32,
+    /// Timestamp in nanoseconds
+    pub last_update: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AssetPoolView{
+    pub shares: U128,
+    pub balance: U128,
+    pub supply_apr_bps: u32,
+}
+
+impl AssetPool{
+    pub fn new(timestamp: u64) -> Self{
+        Self { shares: 0, balance: 0, supply_apr_bps: DEFAULT_SUPPLY_APR_BPS, last_update: timestamp }
+    }
+
+    /// The balance with the interest until `timestamp`
+    pub fn balance_at(&self, timestamp: u64) -> Balance{
+        let elapsed = u128::from(timestamp.saturating_sub(self.last_update));
+        let interest = (U256::from(self.balance) * U256::from(self.supply_apr_bps) * U256::from(elapsed)
+            / (U256::from(BPS_NOMINAL) * U256::from(YEAR_NS))).as_u128();
+
+        return self.balance + interest;
+    }
+
+    pub fn accrue(&mut self, timestamp: u64){
+        self.balance = self.balance_at(timestamp);
+        self.last_update = timestamp;
+    }
+
+    pub fn shares_to_amount(&self, shares: NumShares, balance: Balance) -> Balance{
+        if self.shares == 0{
+            return 0;
+        }
+
+        return mul_div(shares, balance, self.shares);
+    }
+
+    /// Adds `amount` to the accrued pool and returns the minted shares
+    pub fn deposit(&mut self, amount: Balance) -> NumShares{
+        let shares = if self.shares == 0 || self.balance == 0{
+            amount
+        }else{
+            mul_div(amount, self.shares, self.balance)
+        };
+
+        self.shares += shares;
+        self.balance += amount;
+        return shares;
+    }
+
+    /// Takes `amount` out of the accrued pool and returns the burned shares, rounded up
+    /// but not more than `max_shares`
+    pub fn withdraw(&mut self, amount: Balance, max_shares: NumShares) -> NumShares{
+        let shares = std::cmp::min(mul_div_ceil(amount, self.shares, self.balance), max_shares);
+
+        self.shares -= shares;
+        self.balance -= amount;
+        return shares;
+    }
+
+    pub fn to_view(&self, timestamp: u64) -> AssetPoolView{
+        return AssetPoolView { shares: U128(self.shares), balance: U128(self.balance_at(timestamp)), supply_apr_bps: self.supply_apr_bps };
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Default, Clone, Copy, Debug, PartialEq)]
+pub struct AccountAsset{
+    pub shares: NumShares,
+    /// The supplied tokens without the interest
+    pub principal: Balance,
+    /// Tokens sent to the account as rewards, they don't earn interest
+    pub rewards: Balance,
+}
+
+impl AccountAsset{
+    /// Burns shares and the same part of the principal
+    pub fn burn_shares(&mut self, shares: NumShares){
+        assert!(shares <= self.shares, "Not enough shares");
+        self.principal -= mul_div(self.principal, shares, self.shares);
+        self.shares -= shares;
+    }
+}
@@ -23,6 +23,28 @@ pub mod events{
         log!("EVENT_JSON:{}", event.to_string());
     }
 
+    pub fn supply(account_id: &AccountId, amount: Balance, token_id: &AccountId){
+        log_event(
+            "supply", 
+            Event {
+                account_id: &account_id,
+                amount: U128(amount),
+                token_id: &token_id,
+            }
+        );
+    }
+
+    pub fn farm_rewards_claimed(account_id: &AccountId, amount: Balance, token_id: &AccountId){
+        log_event(
+            "farm_rewards_claimed", 
+            Event {
+                account_id: &account_id,
+                amount: U128(amount),
+                token_id: &token_id,
+            }
+        );
+    }
+
     pub fn withdraw_success(account_id: &AccountId, amount: Balance, token_id: &AccountId){
         log_event(
             "withdraw_success", 
@@ -0,0 +1,104 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::LookupMap;
+use near_sdk::serde::{Serialize, Deserialize};
+use near_sdk::{near_bindgen, env, log, Balance, Gas, Promise};
+
+use crate::*;
+
+/// The calls that can be made to fail
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum FailureMethod{
+    Execute,
+    FtOnTransfer,
+    ShowReward,
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum FailureMode{
+    /// The call fails. The failure is a promise that panics, so the count isn't reverted
+    Panic,
+    /// Only `bps` out of 10_000 of the amount is sent, accepted or shown
+    ShortAmount { bps: u32 },
+    /// The call burns `tgas` teragas more
+    ExtraGas { tgas: u64 },
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Failure{
+    pub mode: FailureMode,
+    /// The number of the next calls that fail
+    pub calls: u32,
+}
+
+/// Takes one call from the failure of the method. A new handle on the same storage
+/// is used, so the failures of the views are counted too. A view call with a failure
+/// set can't write the count and panics
+fn next_failure(method: FailureMethod) -> Option<FailureMode>{
+    let mut failures: LookupMap<FailureMethod, Failure> = LookupMap::new(StorageKeys::Failures);
+    let mut failure = failures.get(&method)?;
+
+    failure.calls -= 1;
+    if failure.calls == 0{
+        failures.remove(&method);
+    }else{
+        failures.insert(&method, &failure);
+    }
+
+    log!("Injected failure {:?} of {:?}", failure.mode, method);
+    return Some(failure.mode);
+}
+
+/// Applies the next failure of the method. Returns the part of the amount to use, out of 10_000,
+/// or the failing promise the call should return
+pub(crate) fn apply_failure(method: FailureMethod) -> Result<u32, Promise>{
+    match next_failure(method){
+        None => Ok(BPS_NOMINAL as u32),
+        Some(FailureMode::Panic) => Err(ext_self::fail_injected(method, env::current_account_id(), 0, GAS_FOR_INJECTED_FAILURE)),
+        Some(FailureMode::ShortAmount { bps }) => Ok(bps),
+        Some(FailureMode::ExtraGas { tgas }) => {
+            burn_gas(Gas(Gas::ONE_TERA.0 * tgas));
+            Ok(BPS_NOMINAL as u32)
+        }
+    }
+}
+
+pub(crate) fn short_amount(amount: Balance, bps: u32) -> Balance{
+    return (U256::from(amount) * U256::from(bps) / U256::from(BPS_NOMINAL)).as_u128();
+}
+
+fn burn_gas(gas: Gas){
+    let target = env::used_gas().0 + gas.0;
+    let mut hash = env::sha256_array(&target.to_le_bytes());
+    while env::used_gas().0 < target{
+        hash = env::sha256_array(&hash);
+    }
+}
+
+#[near_bindgen]
+impl Contract{
+    /// Makes the next `calls` calls of the method fail with the mode, 0 calls removes the failure
+    pub fn set_failure(&mut self, method: FailureMethod, mode: FailureMode, calls: u32){
+        assert_eq!(env::predecessor_account_id(), self.owner_id, "Only the owner can set failures");
+        if let FailureMode::ShortAmount { bps } = mode{
+            assert!(u128::from(bps) < BPS_NOMINAL, "The short amount should be less than 100%");
+        }
+
+        if calls == 0{
+            self.failures.remove(&method);
+        }else{
+            self.failures.insert(&method, &Failure { mode: mode, calls: calls });
+        }
+    }
+
+    pub fn get_failure(&self, method: FailureMethod) -> Option<Failure>{
+        return self.failures.get(&method);
+    }
+
+    #[private]
+    pub fn fail_injected(&self, method: FailureMethod){
+        panic!("Injected failure of {:?}", method);
+    }
+}
@@ -0,0 +1,194 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Serialize, Deserialize};
+use near_sdk::json_types::U128;
+use near_sdk::{near_bindgen, env, AccountId, Balance};
+
+use crate::*;
+
+/// The farm rewards per share are scaled to keep the precision
+const REWARD_PER_SHARE_SCALE: u128 = 1_000_000_000_000_000_000_000_000;
+const SECOND_NS: u64 = 1_000_000_000;
+/// The prefix of the message that funds a farm, followed by the supplied token
+pub const FARM_MSG_PREFIX: &str = "farm:";
+
+/// Emits the reward token to the accounts that supply a token, by their shares
+#[derive(BorshDeserialize, BorshSerialize, Clone)]
+pub struct Farm{
+    pub reward_token_id: TokenId,
+    pub reward_per_second: Balance,
+    /// The funded rewards that are not emitted yet
+    pub remaining_rewards: Balance,
+    /// The words of the emitted rewards per share as U256
+    pub reward_per_share: [u64; 4],
+    /// Timestamp in nanoseconds
+    pub last_update: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FarmView{
+    pub reward_token_id: TokenId,
+    pub reward_per_second: U128,
+    pub remaining_rewards: U128,
+}
+
+/// The farm rewards of an account
+#[derive(BorshDeserialize, BorshSerialize, Default, Clone, Copy)]
+pub struct AccountFarm{
+    /// The rewards per share of the farm when the account was last updated
+    pub reward_per_share: [u64; 4],
+    pub unclaimed: Balance,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FarmRewardView{
+    /// The supplied token
+    pub token_id: TokenId,
+    pub reward_token_id: TokenId,
+    pub amount: U128,
+}
+
+impl Farm{
+    /// Emits the rewards until `timestamp` to the `total_shares`
+    pub fn update(&mut self, total_shares: NumShares, timestamp: u64){
+        if timestamp <= self.last_update{
+            return;
+        }
+
+        if total_shares > 0{
+            let elapsed = u128::from(timestamp - self.last_update);
+            let emitted = std::cmp::min(
+                (U256::from(self.reward_per_second) * U256::from(elapsed) / U256::from(SECOND_NS)).as_u128(),
+                self.remaining_rewards);
+
+            self.remaining_rewards -= emitted;
+            self.reward_per_share = (U256(self.reward_per_share) + U256::from(emitted) * U256::from(REWARD_PER_SHARE_SCALE) / U256::from(total_shares)).0;
+        }
+        self.last_update = timestamp;
+    }
+}
+
+impl AccountFarm{
+    /// Adds the rewards of the `shares` since the last update
+    pub fn update(&mut self, farm: &Farm, shares: NumShares){
+        let reward_per_share = U256(farm.reward_per_share) - U256(self.reward_per_share);
+        self.unclaimed += (U256::from(shares) * reward_per_share / U256::from(REWARD_PER_SHARE_SCALE)).as_u128();
+        self.reward_per_share = farm.reward_per_share;
+    }
+}
+
+#[near_bindgen]
+impl Contract{
+    /// Adds a farm of the reward token to the supplied token or changes its emission.
+    /// The farm is funded with `ft_transfer_call` of the reward token and the message `farm:<token_id>`
+    pub fn set_farm(&mut self, token_id: AccountId, reward_token_id: AccountId, reward_per_second: U128){
+        assert_eq!(env::predecessor_account_id(), self.owner_id, "Only the owner can set farms");
+
+        let mut farms = self.get_updated_farms(&token_id);
+        match farms.iter_mut().find(|el| el.reward_token_id == reward_token_id){
+            Some(farm) => farm.reward_per_second = reward_per_second.0,
+            None => farms.push(Farm {
+                reward_token_id: reward_token_id,
+                reward_per_second: reward_per_second.0,
+                remaining_rewards: 0,
+                reward_per_share: [0; 4],
+                last_update: env::block_timestamp(),
+            }),
+        }
+        self.farms.insert(&token_id, &farms);
+    }
+
+    pub fn get_farms(&self, token_id: AccountId) -> Vec<FarmView>{
+        return self.get_updated_farms(&token_id)
+            .iter()
+            .map(|el| FarmView { reward_token_id: el.reward_token_id.clone(), reward_per_second: U128(el.reward_per_second), remaining_rewards: U128(el.remaining_rewards) })
+            .collect();
+    }
+
+    /// The farm rewards of the account that are not claimed yet
+    pub fn get_unclaimed_farm_rewards(&self, account_id: AccountId) -> Vec<FarmRewardView>{
+        let acc = self.get_account(&account_id);
+        let mut rewards = Vec::new();
+
+        for (token_id, asset) in acc.token_id_balance.iter(){
+            for farm in self.get_updated_farms(&token_id){
+                let mut account_farm = acc.farms.get(&(token_id.clone(), farm.reward_token_id.clone())).unwrap_or_default();
+                account_farm.update(&farm, asset.shares);
+                rewards.push(FarmRewardView { token_id: token_id.clone(), reward_token_id: farm.reward_token_id, amount: U128(account_farm.unclaimed) });
+            }
+        }
+
+        return rewards;
+    }
+
+    /// Moves the farm rewards of the account to its rewards, so they can be withdrawn
+    pub fn account_farm_claim_all(&mut self, account_id: Option<AccountId>){
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        let mut acc = self.get_account(&account_id);
+        let tokens = acc.token_id_balance.keys_as_vector().to_vec();
+
+        for token_id in tokens.iter(){
+            let asset = acc.token_id_balance.get(token_id).unwrap_or_default();
+            for farm in self.internal_update_farms(&mut acc, token_id, asset.shares){
+                let key = (token_id.clone(), farm.reward_token_id.clone());
+                let mut account_farm = acc.farms.get(&key).unwrap_or_default();
+                if account_farm.unclaimed == 0{
+                    continue;
+                }
+
+                let mut reward = acc.token_id_balance.get(&farm.reward_token_id).unwrap_or_default();
+                reward.rewards += account_farm.unclaimed;
+                acc.token_id_balance.insert(&farm.reward_token_id, &reward);
+                events::events::farm_rewards_claimed(&account_id, account_farm.unclaimed, &farm.reward_token_id);
+
+                account_farm.unclaimed = 0;
+                acc.farms.insert(&key, &account_farm);
+            }
+        }
+        self.accounts.insert(&account_id, &acc);
+    }
+}
+
+impl Contract{
+    /// The farms of the supplied token with the rewards emitted until now
+    fn get_updated_farms(&self, token_id: &TokenId) -> Vec<Farm>{
+        let total_shares = self.get_asset(token_id).map_or(0, |pool| pool.shares);
+        let mut farms = self.farms.get(token_id).unwrap_or_default();
+        for farm in farms.iter_mut(){
+            farm.update(total_shares, env::block_timestamp());
+        }
+
+        return farms;
+    }
+
+    /// Emits the farm rewards until now and adds the rewards of the account shares to
+    /// its unclaimed rewards. Should be called before the shares of the token change
+    pub(crate) fn internal_update_farms(&mut self, acc: &mut TokensBalances, token_id: &TokenId, account_shares: NumShares) -> Vec<Farm>{
+        let farms = self.get_updated_farms(token_id);
+        if farms.is_empty(){
+            return farms;
+        }
+
+        for farm in farms.iter(){
+            let key = (token_id.clone(), farm.reward_token_id.clone());
+            let mut account_farm = acc.farms.get(&key).unwrap_or_default();
+            account_farm.update(farm, account_shares);
+            acc.farms.insert(&key, &account_farm);
+        }
+        self.farms.insert(token_id, &farms);
+
+        return farms;
+    }
+
+    pub(crate) fn internal_fund_farm(&mut self, token_id: &TokenId, reward_token_id: &TokenId, amount: Balance){
+        let mut farms = self.get_updated_farms(token_id);
+        let farm = farms
+            .iter_mut()
+            .find(|el| &el.reward_token_id == reward_token_id)
+            .unwrap_or_else(|| panic!("There is no {} farm of {}", reward_token_id, token_id));
+
+        farm.remaining_rewards += amount;
+        self.farms.insert(token_id, &farms);
+    }
+}
@@ -1,17 +1,29 @@
 use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
 
-use near_sdk::collections::{ UnorderedMap};
+use near_sdk::collections::{LookupMap, UnorderedMap};
 use near_sdk::json_types::{U128};
 use near_sdk::serde::{Serialize, Deserialize};
-use near_sdk::{self, near_bindgen, ext_contract, log, env, AccountId, Balance, PromiseResult, Gas, Promise};
+use near_sdk::{self, near_bindgen, ext_contract, log, env, AccountId, Balance, PromiseResult, PromiseOrValue, Gas, Promise, BorshStorageKey, CryptoHash};
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use std::vec::Vec;
 
 pub const ON_FUNDING_GAS: Gas = near_sdk::Gas(10_000_000_000_000);
 
+pub const GAS_FOR_AFTER_FT_TRANSFER: Gas = near_sdk::Gas(10_000_000_000_000);
+
+pub const GAS_FOR_INJECTED_FAILURE: Gas = near_sdk::Gas(5_000_000_000_000);
+
 pub const GAS_FOR_FT_TRANSFER: Gas = near_sdk::Gas(50_000_000_000_000);
 
 mod events;
+mod asset;
+mod failures;
+mod farms;
+
+use asset::{AssetPool, AssetPoolView, AccountAsset, BPS_NOMINAL};
+use common::types::U256;
+use failures::{FailureMethod, Failure, apply_failure, short_amount};
+use farms::{Farm, AccountFarm, FARM_MSG_PREFIX};
 
 pub(crate) type TokenId = AccountId;
 pub(crate) type NumShares = Balance;
@@ -31,21 +43,35 @@ pub struct AssetAmount {
 #[derive(Deserialize, Debug, Serialize)]
 #[serde(crate = "near_sdk::serde")]
 pub enum Action{
-    Withdraw(AssetAmount)
+    /// Moves tokens sent as rewards to the supply, so they earn interest
+    Supply(AssetAmount),
+    /// Sends tokens to the caller, first from the rewards and then from the supply
+    Withdraw(AssetAmount),
+}
+
+#[derive(BorshStorageKey, BorshSerialize)]
+pub enum StorageKeys {
+    Accounts,
+    AccountTokens {account_hash: CryptoHash},
+    Assets,
+    Failures,
+    Farms,
+    AccountFarms {account_hash: CryptoHash},
 }
 
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct TokensBalances{
-    /// first variable shows the balance, second shows the reward tally
-    pub token_id_balance: UnorderedMap<AccountId, (NumShares, Balance)>,
-    pub tally_below_zero: bool,
+    pub token_id_balance: UnorderedMap<TokenId, AccountAsset>,
+    /// The farm rewards by the supplied token and the reward token
+    pub farms: UnorderedMap<(TokenId, TokenId), AccountFarm>,
 }
 
-impl Default for TokensBalances {
-    fn default() -> Self {
+impl TokensBalances {
+    fn new(account_id: &AccountId) -> Self {
+        let account_hash = env::sha256_array(account_id.as_bytes());
         Self { 
-            token_id_balance: UnorderedMap::new(b"tib".to_vec()),
-            tally_below_zero: false 
+            token_id_balance: UnorderedMap::new(StorageKeys::AccountTokens { account_hash: account_hash }),
+            farms: UnorderedMap::new(StorageKeys::AccountFarms { account_hash: account_hash }),
         }
     }
 }
@@ -54,13 +80,24 @@ impl Default for TokensBalances {
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct Contract {
+    pub owner_id: AccountId,
     pub accounts: UnorderedMap<AccountId, TokensBalances>,
+    /// The supplied tokens and the interest of every token
+    pub assets: UnorderedMap<TokenId, AssetPool>,
+    /// The failures injected in the next calls of the methods
+    pub failures: LookupMap<FailureMethod, Failure>,
+    /// The farms of every supplied token
+    pub farms: UnorderedMap<TokenId, Vec<Farm>>,
 } 
 
 impl Default for Contract{
     fn default() -> Self {
         Self {
-            accounts:UnorderedMap::new(b"a"),
+            owner_id: env::current_account_id(),
+            accounts: UnorderedMap::new(StorageKeys::Accounts),
+            assets: UnorderedMap::new(StorageKeys::Assets),
+            failures: LookupMap::new(StorageKeys::Failures),
+            farms: UnorderedMap::new(StorageKeys::Farms),
         }
     }
 }
@@ -79,6 +116,7 @@ pub trait ExtFt {
 #[ext_contract(ext_self)]
 pub trait ExtSelf{
     fn on_after_ft_transfer(&mut self, account_id: AccountId, token_id: AccountId, amount: U128) -> bool;
+    fn fail_injected(&self, method: FailureMethod);
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -95,42 +133,72 @@ impl Contract{
     pub fn new() -> Self{
         assert!(!env::state_exists(), "Already initialized");
 
-        Contract::default()
+        Self {
+            owner_id: env::predecessor_account_id(),
+            ..Contract::default()
+        }
     }
 
-    pub fn show_reward(&self, account_id: AccountId) -> Vec<TokenAmountsView>{
-        let acc: TokensBalances = self
-            .accounts
-            .get(&account_id)
-            .unwrap_or_default();
+    /// The supplied tokens of the account as `shares` and the accrued interest with
+    /// the tokens sent as rewards as `rewards`
+    pub fn show_reward(&self, account_id: AccountId) -> PromiseOrValue<Vec<TokenAmountsView>>{
+        let bps = match apply_failure(FailureMethod::ShowReward){
+            Ok(bps) => bps,
+            Err(failed) => return PromiseOrValue::Promise(failed),
+        };
+        let acc = self.get_account(&account_id);
+        let now = env::block_timestamp();
         
-        return acc.token_id_balance.iter().map(|(token_id, (share, reward))|{
-            TokenAmountsView { token: token_id, shares: U128(share), rewards: U128(reward) }
-        }).collect::<Vec<TokenAmountsView>>();
+        return PromiseOrValue::Value(acc.token_id_balance.iter().map(|(token_id, asset)|{
+            let value = self.get_asset(&token_id).map_or(0, |pool| pool.shares_to_amount(asset.shares, pool.balance_at(now)));
+            let interest = value.saturating_sub(asset.principal);
+            TokenAmountsView { token: token_id, shares: U128(short_amount(asset.principal, bps)), rewards: U128(short_amount(interest + asset.rewards, bps)) }
+        }).collect::<Vec<TokenAmountsView>>());
+    }
+
+    pub fn get_asset_pool(&self, token_id: AccountId) -> Option<AssetPoolView>{
+        return self.get_asset(&token_id).map(|pool| pool.to_view(env::block_timestamp()));
     }
 
-    pub fn account_farm_claim_all(&self){}
+    /// Sets the yearly interest of the supplied tokens, the interest until now is accrued with the old APR
+    pub fn set_supply_apr(&mut self, token_id: AccountId, apr_bps: u32){
+        assert_eq!(env::predecessor_account_id(), self.owner_id, "Only the owner can set the APR");
+        assert!(u128::from(apr_bps) <= BPS_NOMINAL * 10, "The APR is too big");
+
+        let mut pool = self.get_accrued_asset(&token_id);
+        pool.supply_apr_bps = apr_bps;
+        self.assets.insert(&token_id, &pool);
+    }
 
     #[payable]
-    pub fn execute(&mut self, actions: Vec<Action>){
+    pub fn execute(&mut self, actions: Vec<Action>) -> PromiseOrValue<()>{
         let caller = env::predecessor_account_id();
         assert!(env::attached_deposit() >= 1, "1 yocto for ft transfer is needed");
+        let bps = match apply_failure(FailureMethod::Execute){
+            Ok(bps) => bps,
+            Err(failed) => return PromiseOrValue::Promise(failed),
+        };
 
-        let acc_amounts = self.accounts.get(&caller).unwrap_or_default();
-        
         for action in actions{
             match action{
+                Action::Supply(asset_amount) => {
+                    let asset = self.get_account(&caller).token_id_balance.get(&asset_amount.token_id).unwrap_or_default();
+                    let amount = get_action_amount(&asset_amount, asset.rewards);
+                    self.internal_supply_rewards(&caller, &asset_amount.token_id, amount);
+                }
                 Action::Withdraw(asset_amount) => {
-                    let token_amount = acc_amounts.token_id_balance.get(&asset_amount.token_id).unwrap_or_default();
-
-                    if asset_amount.amount.unwrap().0 > token_amount.1{
-                        panic!("Trying to send amount that is not available");
-                    }
+                    let amount = get_action_amount(&asset_amount, self.get_available_amount(&caller, &asset_amount.token_id));
+                    self.internal_withdraw(&caller, &asset_amount.token_id, amount);
+                    // with a short amount failure the rest is kept by the contract
+                    let amount = short_amount(amount, bps);
 
-                    ext_fungible_token::ft_transfer(caller.clone(), asset_amount.amount.unwrap().0.to_string(), None, asset_amount.token_id, 1, GAS_FOR_FT_TRANSFER);
+                    ext_fungible_token::ft_transfer(caller.clone(), amount.to_string(), None, asset_amount.token_id.clone(), 1, GAS_FOR_FT_TRANSFER)
+                        .then(ext_self::on_after_ft_transfer(caller.clone(), asset_amount.token_id, U128(amount), env::current_account_id(), 0, GAS_FOR_AFTER_FT_TRANSFER));
                 }
             }   
         }       
+
+        return PromiseOrValue::Value(());
     }
  
  
@@ -144,14 +212,9 @@ impl Contract{
 
         if transfer_success{
             events::events::withdraw_success(&account_id, amount.0, &token_id);
-
-            let mut acc_amount = self.accounts.get(&account_id).unwrap_or_default();
-            let mut amounts = acc_amount.token_id_balance.get(&token_id).unwrap_or_default();
-            amounts.1 += amount.0;
-            acc_amount.token_id_balance.insert(&token_id, &amounts);
-            self.accounts.insert(&account_id, &acc_amount);
-
         }else {
+            // the tokens are back in the contract, supply them again like Burrow does
+            self.internal_supply(&account_id, &token_id, amount.0);
             events::events::withdraw_failed(&account_id, amount.0, &token_id);
         }
 
@@ -203,6 +266,101 @@ impl Contract{
     }*/
 }
 
+impl Contract{
+    fn get_account(&self, account_id: &AccountId) -> TokensBalances{
+        return self.accounts.get(account_id).unwrap_or_else(|| TokensBalances::new(account_id));
+    }
+
+    fn get_asset(&self, token_id: &TokenId) -> Option<AssetPool>{
+        return self.assets.get(token_id);
+    }
+
+    /// The asset pool with the interest until the current block
+    fn get_accrued_asset(&self, token_id: &TokenId) -> AssetPool{
+        let now = env::block_timestamp();
+        let mut pool = self.get_asset(token_id).unwrap_or_else(|| AssetPool::new(now));
+        pool.accrue(now);
+
+        return pool;
+    }
+
+    /// The rewards sent to the account and the value of its supplied shares
+    fn get_available_amount(&self, account_id: &AccountId, token_id: &TokenId) -> Balance{
+        let asset = self.get_account(account_id).token_id_balance.get(token_id).unwrap_or_default();
+        let value = self.get_asset(token_id).map_or(0, |pool| pool.shares_to_amount(asset.shares, pool.balance_at(env::block_timestamp())));
+
+        return asset.rewards + value;
+    }
+
+    /// Mints shares at the current price for the supplied amount
+    fn internal_supply(&mut self, account_id: &AccountId, token_id: &TokenId, amount: Balance){
+        let mut acc = self.get_account(account_id);
+        let mut asset = acc.token_id_balance.get(token_id).unwrap_or_default();
+        let mut pool = self.get_accrued_asset(token_id);
+        self.internal_update_farms(&mut acc, token_id, asset.shares);
+
+        asset.shares += pool.deposit(amount);
+        asset.principal += amount;
+
+        acc.token_id_balance.insert(token_id, &asset);
+        self.accounts.insert(account_id, &acc);
+        self.assets.insert(token_id, &pool);
+    }
+
+    fn internal_supply_rewards(&mut self, account_id: &AccountId, token_id: &TokenId, amount: Balance){
+        let mut acc = self.get_account(account_id);
+        let mut asset = acc.token_id_balance.get(token_id).unwrap_or_default();
+        if amount > asset.rewards{
+            panic!("Trying to supply amount that is not available");
+        }
+
+        asset.rewards -= amount;
+        acc.token_id_balance.insert(token_id, &asset);
+        self.accounts.insert(account_id, &acc);
+
+        self.internal_supply(account_id, token_id, amount);
+        events::events::supply(account_id, amount, token_id);
+    }
+
+    /// Takes the amount from the rewards sent to the account and then burns
+    /// the supplied shares at the current price
+    fn internal_withdraw(&mut self, account_id: &AccountId, token_id: &TokenId, amount: Balance){
+        let mut acc = self.get_account(account_id);
+        let mut asset = acc.token_id_balance.get(token_id).unwrap_or_default();
+        let mut pool = self.get_accrued_asset(token_id);
+
+        let from_rewards = std::cmp::min(asset.rewards, amount);
+        let from_supply = amount - from_rewards;
+        if from_supply > pool.shares_to_amount(asset.shares, pool.balance){
+            panic!("Trying to send amount that is not available");
+        }
+
+        asset.rewards -= from_rewards;
+        if from_supply > 0{
+            self.internal_update_farms(&mut acc, token_id, asset.shares);
+            let shares = pool.withdraw(from_supply, asset.shares);
+            asset.burn_shares(shares);
+        }
+
+        acc.token_id_balance.insert(token_id, &asset);
+        self.accounts.insert(account_id, &acc);
+        self.assets.insert(token_id, &pool);
+    }
+}
+
+/// The amount of an action like in Burrow, `amount` if it is set or else
+/// the available amount, but not more than `max_amount`
+fn get_action_amount(asset_amount: &AssetAmount, available: Balance) -> Balance{
+    let amount = match (asset_amount.amount, asset_amount.max_amount){
+        (Some(amount), _) => amount.0,
+        (None, Some(max_amount)) => std::cmp::min(max_amount.0, available),
+        (None, None) => available,
+    };
+    assert!(amount > 0, "Amount can't be 0");
+
+    return amount;
+}
+
 #[near_bindgen]
 impl FungibleTokenReceiver for Contract{
     fn ft_on_transfer(
@@ -211,44 +369,250 @@ impl FungibleTokenReceiver for Contract{
         amount: near_sdk::json_types::U128,
         msg: String,
     ) -> near_sdk::PromiseOrValue<near_sdk::json_types::U128> {
+        if let Some(farm_token_id) = msg.strip_prefix(FARM_MSG_PREFIX){
+            self.internal_fund_farm(&AccountId::new_unchecked(farm_token_id.to_string()), &env::predecessor_account_id(), amount.0);
+            return near_sdk::PromiseOrValue::Value(U128(0));
+        }
+
         let acc_id: AccountId = if msg.is_empty() {
             sender_id.clone()
         }else{
             AccountId::new_unchecked(msg.clone())
         };
 
+        let bps = match apply_failure(FailureMethod::FtOnTransfer){
+            Ok(bps) => bps,
+            Err(failed) => return near_sdk::PromiseOrValue::Promise(failed),
+        };
+        // with a short amount failure the rest is returned to the sender
+        let unused_amount = amount.0 - short_amount(amount.0, bps);
+        let amount = U128(amount.0 - unused_amount);
+
         let token_id = env::predecessor_account_id();
         log!("Predecesor {} Signer {} Sender {} amount {} acc_id {}", env::predecessor_account_id(), env::signer_account_id(), &sender_id, amount.0, acc_id);
-        let mut account_tokens: TokensBalances = self.accounts.get(&acc_id).unwrap_or_default();
-        let mut token_balance: (NumShares, Balance) = account_tokens.token_id_balance.get(&token_id).unwrap_or_default();
         if msg.is_empty(){
-            token_balance.0 += amount.0;
+            self.internal_supply(&acc_id, &token_id, amount.0);
+            events::events::supply(&acc_id, amount.0, &token_id);
         }else{
-            token_balance.1 += amount.0;
+            let mut account_tokens = self.get_account(&acc_id);
+            let mut token_balance = account_tokens.token_id_balance.get(&token_id).unwrap_or_default();
+            token_balance.rewards += amount.0;
+            account_tokens.token_id_balance.insert(&token_id, &token_balance);
+            self.accounts.insert(&acc_id, &account_tokens);
         }
-        account_tokens.token_id_balance.insert(&token_id, &token_balance);
-        self.accounts.insert(&acc_id, &account_tokens);
 
-        return near_sdk::PromiseOrValue::Value(U128(0));
+        return near_sdk::PromiseOrValue::Value(U128(unused_amount));
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use near_sdk::{json_types::U128, AccountId};
+    use near_sdk::{json_types::U128, AccountId, testing_env, PromiseResult, PromiseOrValue, VMConfig, RuntimeFeesConfig};
+    use near_sdk::test_utils::VMContextBuilder;
     use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
 
-    use crate::Contract;
+    use crate::{Contract, Action, AssetAmount, TokenAmountsView};
+    use crate::failures::{FailureMethod, FailureMode};
+    use crate::asset::YEAR_NS;
+
+    const SECOND_NS: u64 = 1_000_000_000;
+
+    fn account(name: &str) -> AccountId{
+        AccountId::new_unchecked(name.to_string())
+    }
+
+    fn show_reward(contract: &Contract, account_id: AccountId) -> Vec<TokenAmountsView>{
+        match contract.show_reward(account_id){
+            PromiseOrValue::Value(position) => position,
+            PromiseOrValue::Promise(_) => panic!("The position should be a value"),
+        }
+    }
+
+    fn asset_amount(amount: Option<u128>, max_amount: Option<u128>) -> AssetAmount{
+        return AssetAmount { token_id: account("usdc.near"), amount: amount.map(U128), max_amount: max_amount.map(U128) };
+    }
+
+    fn set_context(predecessor: AccountId, timestamp: u64){
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account("defi.near"))
+            .predecessor_account_id(predecessor)
+            .block_timestamp(timestamp)
+            .attached_deposit(1)
+            .build());
+    }
 
     #[test]
     fn it_works() {
         let x = vec![1,2,3];
         let mut contract = Contract::default();
         let res = contract.ft_on_transfer(AccountId::new_unchecked("test.near".to_string()), U128(10), "".to_string());
-        let shares = contract.show_reward(AccountId::new_unchecked("test.near".to_string())).first().unwrap().shares;
+        let shares = show_reward(&contract, AccountId::new_unchecked("test.near".to_string())).first().unwrap().shares;
         assert_eq!(shares.0, 10);
         contract.ft_on_transfer(AccountId::new_unchecked("test.near".to_string()), U128(5), "test.near".to_string());
-        let reward = contract.show_reward(AccountId::new_unchecked("test.near".to_string())).first().unwrap().rewards;
+        let reward = show_reward(&contract, AccountId::new_unchecked("test.near".to_string())).first().unwrap().rewards;
         assert_eq!(reward.0, 5);
     }
+
+    #[test]
+    fn test_interest_accrual() {
+        set_context(account("owner.near"), 0);
+        let mut contract = Contract::new();
+        contract.set_supply_apr(account("usdc.near"), 1000);
+
+        set_context(account("usdc.near"), 0);
+        contract.ft_on_transfer(account("alice.near"), U128(1000), "".to_string());
+
+        // half a year at 10% APR
+        set_context(account("usdc.near"), YEAR_NS / 2);
+        let position = show_reward(&contract, account("alice.near"));
+        assert_eq!(position[0].shares, U128(1000));
+        assert_eq!(position[0].rewards, U128(50));
+
+        // the new supply gets fewer shares for the same amount
+        contract.ft_on_transfer(account("bob.near"), U128(1050), "".to_string());
+        assert_eq!(contract.get_asset_pool(account("usdc.near")).unwrap().shares, U128(2000));
+
+        set_context(account("alice.near"), YEAR_NS / 2);
+        contract.execute(vec![Action::Withdraw(AssetAmount { token_id: account("usdc.near"), amount: Some(U128(525)), max_amount: None })]);
+        let position = show_reward(&contract, account("alice.near"));
+        assert_eq!(position[0].shares, U128(500));
+        assert_eq!(position[0].rewards, U128(25));
+        assert_eq!(contract.get_asset_pool(account("usdc.near")).unwrap().balance, U128(1575));
+    }
+
+    #[test]
+    #[should_panic(expected = "Trying to send amount that is not available")]
+    fn test_withdraw_more_than_supplied() {
+        set_context(account("usdc.near"), 0);
+        let mut contract = Contract::default();
+        contract.ft_on_transfer(account("alice.near"), U128(1000), "".to_string());
+
+        set_context(account("alice.near"), YEAR_NS);
+        contract.execute(vec![Action::Withdraw(AssetAmount { token_id: account("usdc.near"), amount: Some(U128(1051)), max_amount: None })]);
+    }
+
+    #[test]
+    fn test_supply_and_withdraw_max_amount() {
+        set_context(account("usdc.near"), 0);
+        let mut contract = Contract::default();
+        contract.ft_on_transfer(account("alice.near"), U128(1000), "".to_string());
+        contract.ft_on_transfer(account("sender.near"), U128(300), "alice.near".to_string());
+
+        set_context(account("alice.near"), 0);
+        contract.execute(vec![Action::Supply(asset_amount(None, Some(200)))]);
+        let position = show_reward(&contract, account("alice.near"));
+        assert_eq!(position[0].shares, U128(1200));
+        assert_eq!(position[0].rewards, U128(100));
+
+        // without an amount everything is withdrawn, up to max_amount
+        contract.execute(vec![Action::Withdraw(asset_amount(None, Some(400)))]);
+        let position = show_reward(&contract, account("alice.near"));
+        assert_eq!(position[0].shares, U128(900));
+        assert_eq!(position[0].rewards, U128(0));
+
+        contract.execute(vec![Action::Withdraw(asset_amount(None, None))]);
+        assert_eq!(show_reward(&contract, account("alice.near"))[0].shares, U128(0));
+        assert_eq!(contract.get_asset_pool(account("usdc.near")).unwrap().balance, U128(0));
+    }
+
+    #[test]
+    fn test_withdraw_restored_only_on_failed_transfer() {
+        set_context(account("usdc.near"), 0);
+        let mut contract = Contract::default();
+        contract.ft_on_transfer(account("alice.near"), U128(1000), "".to_string());
+
+        set_context(account("alice.near"), 0);
+        contract.execute(vec![Action::Withdraw(asset_amount(Some(400), None))]);
+        assert_eq!(show_reward(&contract, account("alice.near"))[0].shares, U128(600));
+
+        testing_env!(VMContextBuilder::new().current_account_id(account("defi.near")).predecessor_account_id(account("defi.near")).build(),
+            VMConfig::test(), RuntimeFeesConfig::test(), Default::default(), vec![PromiseResult::Successful(vec![])]);
+        assert!(contract.on_after_ft_transfer(account("alice.near"), account("usdc.near"), U128(400)));
+        assert_eq!(show_reward(&contract, account("alice.near"))[0].shares, U128(600));
+
+        testing_env!(VMContextBuilder::new().current_account_id(account("defi.near")).predecessor_account_id(account("defi.near")).build(),
+            VMConfig::test(), RuntimeFeesConfig::test(), Default::default(), vec![PromiseResult::Failed]);
+        assert!(!contract.on_after_ft_transfer(account("alice.near"), account("usdc.near"), U128(400)));
+        assert_eq!(show_reward(&contract, account("alice.near"))[0].shares, U128(1000));
+        assert_eq!(contract.get_asset_pool(account("usdc.near")).unwrap().balance, U128(1000));
+    }
+
+    #[test]
+    #[should_panic(expected = "Trying to supply amount that is not available")]
+    fn test_supply_more_than_rewards() {
+        set_context(account("usdc.near"), 0);
+        let mut contract = Contract::default();
+        contract.ft_on_transfer(account("sender.near"), U128(100), "alice.near".to_string());
+
+        set_context(account("alice.near"), 0);
+        contract.execute(vec![Action::Supply(asset_amount(Some(101), None))]);
+    }
+
+    #[test]
+    fn test_injected_failures() {
+        set_context(account("owner.near"), 0);
+        let mut contract = Contract::new();
+        contract.set_failure(FailureMethod::FtOnTransfer, FailureMode::ShortAmount { bps: 4000 }, 1);
+        contract.set_failure(FailureMethod::Execute, FailureMode::Panic, 2);
+
+        set_context(account("usdc.near"), 0);
+        match contract.ft_on_transfer(account("alice.near"), U128(1000), "".to_string()){
+            PromiseOrValue::Value(unused) => assert_eq!(unused, U128(600)),
+            PromiseOrValue::Promise(_) => panic!("The unused amount should be a value"),
+        }
+        // the failure is used up
+        assert_eq!(contract.get_failure(FailureMethod::FtOnTransfer), None);
+        contract.ft_on_transfer(account("alice.near"), U128(600), "".to_string());
+        assert_eq!(show_reward(&contract, account("alice.near"))[0].shares, U128(1000));
+
+        set_context(account("alice.near"), 0);
+        for calls in (0..2).rev(){
+            assert!(matches!(contract.execute(vec![Action::Withdraw(asset_amount(Some(100), None))]), PromiseOrValue::Promise(_)));
+            assert_eq!(contract.get_failure(FailureMethod::Execute).map_or(0, |el| el.calls), calls);
+        }
+        assert_eq!(show_reward(&contract, account("alice.near"))[0].shares, U128(1000));
+
+        contract.execute(vec![Action::Withdraw(asset_amount(Some(100), None))]);
+        assert_eq!(show_reward(&contract, account("alice.near"))[0].shares, U128(900));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the owner can set failures")]
+    fn test_set_failure_not_owner() {
+        set_context(account("owner.near"), 0);
+        let mut contract = Contract::new();
+
+        set_context(account("alice.near"), 0);
+        contract.set_failure(FailureMethod::ShowReward, FailureMode::Panic, 1);
+    }
+
+    #[test]
+    fn test_farm_rewards() {
+        set_context(account("owner.near"), 0);
+        let mut contract = Contract::new();
+        contract.set_farm(account("usdc.near"), account("brrr.near"), U128(10));
+
+        set_context(account("brrr.near"), 0);
+        contract.ft_on_transfer(account("owner.near"), U128(1000), "farm:usdc.near".to_string());
+
+        set_context(account("usdc.near"), 0);
+        contract.ft_on_transfer(account("alice.near"), U128(100), "".to_string());
+        set_context(account("usdc.near"), 10 * SECOND_NS);
+        contract.ft_on_transfer(account("bob.near"), U128(300), "".to_string());
+
+        set_context(account("alice.near"), 20 * SECOND_NS);
+        assert_eq!(contract.get_unclaimed_farm_rewards(account("alice.near"))[0].amount, U128(125));
+        assert_eq!(contract.get_unclaimed_farm_rewards(account("bob.near"))[0].amount, U128(75));
+        assert_eq!(contract.get_farms(account("usdc.near"))[0].remaining_rewards, U128(800));
+
+        contract.account_farm_claim_all(None);
+        assert_eq!(contract.get_unclaimed_farm_rewards(account("alice.near"))[0].amount, U128(0));
+        let position = show_reward(&contract, account("alice.near"));
+        let reward = position.iter().find(|el| el.token == account("brrr.near")).unwrap();
+        assert_eq!(reward.rewards, U128(125));
+
+        contract.execute(vec![Action::Withdraw(AssetAmount { token_id: account("brrr.near"), amount: None, max_amount: None })]);
+        let position = show_reward(&contract, account("alice.near"));
+        assert_eq!(position.iter().find(|el| el.token == account("brrr.near")).unwrap().rewards, U128(0));
+    }
 }
@@ -0,0 +1,49 @@
+use crate::*;
+use near_sdk::serde::{Serialize, Deserialize};
+use common::access_control::ContractRole;
+use common::impl_access_control;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Role{
+    /// Manages keepers
+    Admin,
+    /// Starts and completes the draws
+    Keeper,
+}
+
+impl ContractRole for Role{
+    fn admin() -> Self{
+        return Role::Admin;
+    }
+}
+
+impl_access_control!(Contract, access_control, Role, "pool-together-draw");
+
+#[cfg(test)]
+mod tests{
+    use crate::*;
+    use crate::access_control::Role;
+    use crate::test_utils::{alice, bob, owner, set_caller};
+
+    #[test]
+    fn test_keeper_can_start_draw(){
+        set_caller(owner());
+        let mut contract = Contract::new(owner());
+        contract.grant_role(Role::Keeper, alice());
+
+        set_caller(alice());
+        contract.start_draw();
+        assert!(contract.is_started);
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller doesn't have the Keeper role")]
+    fn test_only_keeper_can_start_draw(){
+        set_caller(owner());
+        let mut contract = Contract::new(owner());
+
+        set_caller(bob());
+        contract.start_draw();
+    }
+}
@@ -1,10 +1,13 @@
 use common::generic_ring_buffer::{GenericRingBuffer, RingBuffer};
 use common::types::{DrawId, U256};
-use near_sdk::{env, near_bindgen, EpochHeight, PanicOnDefault};
+use near_sdk::{env, near_bindgen, AccountId, EpochHeight, PanicOnDefault};
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use interfaces::draw::{DrawCreator, Draw, DrawBuffer, DrawRegister};
+use access_control::Role;
+use common::access_control::AccessControl;
 
 mod interfaces;
+mod access_control;
 
 const DRAW_DURATION_IN_EPOCHS: u64 = 5;
 const DRAW_BUFFER_CAPACITY:usize = 3;
@@ -19,6 +22,7 @@ pub struct Contract{
     pub last_epoch_started: EpochHeight,
     pub is_started: bool,
     pub temp_draw: Draw,
+    pub access_control: AccessControl<Role>,
 }
 
 fn as_u256(arr: &[u8; 32]) -> U256{
@@ -43,12 +47,13 @@ fn random_u256() -> U256{
 #[near_bindgen]
 impl Contract{
     #[init]
-    pub fn new() -> Self{
+    pub fn new(owner_id: AccountId) -> Self{
         Self { 
             draw_buffer: GenericRingBuffer::<Draw, DRAW_BUFFER_CAPACITY>::default(), 
             last_epoch_started: 0, 
             is_started: false, 
             temp_draw: Draw::default(),
+            access_control: AccessControl::new(owner_id, b"r".to_vec()),
         }
     }
 
@@ -89,6 +94,7 @@ impl DrawCreator for Contract{
     }
 
     fn start_draw(&mut self) {
+        self.assert_role(Role::Keeper);
         if !self.can_start_draw(){
             return;
         }
@@ -100,6 +106,7 @@ impl DrawCreator for Contract{
     }
 
     fn complete_draw(&mut self) {
+        self.assert_role(Role::Keeper);
         if !self.can_complete_draw() {
             return;
         }
@@ -1,5 +1,6 @@
 use near_sdk::AccountId;
 use near_sdk::Balance;
+pub use common::test_utils::set_caller;
 
 pub fn staking() -> AccountId {
     "staking".parse().unwrap()
@@ -87,10 +88,11 @@ pub mod tests {
         ) -> Self {
             let context = VMContextBuilder::new()
                 .current_account_id(owner())
+                .predecessor_account_id(owner())
                 .account_balance(ntoy(10))
                 .build();
             testing_env!(context.clone());
-            let contract = Contract::new();
+            let contract = Contract::new(owner());
             Emulator {
                 contract,
                 epoch_height: 0,
@@ -103,6 +105,7 @@ pub mod tests {
         pub fn update_context(&mut self, random_seed: [u8; 32]) {
             self.context = VMContextBuilder::new()
                 .current_account_id(staking())
+                .predecessor_account_id(owner())
                 .epoch_height(self.epoch_height)
                 .block_index(self.block_index)
                 .block_timestamp(self.block_timestamp)
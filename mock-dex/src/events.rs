@@ -0,0 +1,40 @@
+pub mod events{
+    use near_sdk::json_types::U128;
+    use near_sdk::{AccountId, Balance, log};
+    use near_sdk::serde::{Serialize};
+    use near_sdk::serde_json::json;
+
+    #[derive(Serialize)]
+    #[serde(crate = "near_sdk::serde")]
+    struct SwapEvent<'a> {
+        pub account_id: &'a AccountId,
+        pub token_in: &'a AccountId,
+        pub amount_in: U128,
+        pub token_out: &'a AccountId,
+        pub amount_out: U128,
+    }
+
+    fn log_event<T: Serialize>(event: &str, data: T) {
+        let event = json!({
+            "standard": "mock-dex",
+            "version": "1.0.0",
+            "event": event,
+            "data": [data]
+        });
+
+        log!("EVENT_JSON:{}", event.to_string());
+    }
+
+    pub fn swap(account_id: &AccountId, token_in: &AccountId, amount_in: Balance, token_out: &AccountId, amount_out: Balance){
+        log_event(
+            "swap",
+            SwapEvent {
+                account_id: &account_id,
+                token_in: &token_in,
+                amount_in: U128(amount_in),
+                token_out: &token_out,
+                amount_out: U128(amount_out),
+            }
+        );
+    }
+}
@@ -0,0 +1,278 @@
+use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
+use near_sdk::collections::{LookupMap, Vector};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Serialize, Deserialize};
+use near_sdk::{self, near_bindgen, ext_contract, env, AccountId, Balance, Gas, Promise, PromiseOrValue, PromiseResult, assert_one_yocto};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use common::types::U256;
+
+const FEE_DIVISOR: u32 = 10_000;
+const GAS_FOR_FT_TRANSFER: Gas = Gas(10_000_000_000_000);
+const GAS_FOR_AFTER_FT_TRANSFER: Gas = Gas(10_000_000_000_000);
+
+mod events;
+
+/// A constant product pool of two tokens
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SimplePool{
+    pub token_account_ids: Vec<AccountId>,
+    pub amounts: Vec<U128>,
+    /// The fee of a swap, out of 10_000
+    pub total_fee: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SwapAction{
+    pub pool_id: u64,
+    pub token_in: AccountId,
+    /// If `None`, the output of the previous action is used
+    pub amount_in: Option<U128>,
+    pub token_out: AccountId,
+    pub min_amount_out: U128,
+}
+
+#[ext_contract(ext_fungible_token)]
+pub trait ExtFt {
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>);
+}
+
+#[ext_contract(ext_self)]
+pub trait ExtSelf{
+    fn exchange_callback_post_withdraw(&mut self, token_id: AccountId, sender_id: AccountId, amount: U128);
+}
+
+/// A simplified Ref Finance exchange. The tokens are deposited with `ft_transfer_call`,
+/// swapped between the deposits and taken out with `withdraw`
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Contract {
+    pub owner_id: AccountId,
+    pub pools: Vector<SimplePool>,
+    /// The deposit of every account and token
+    pub deposits: LookupMap<(AccountId, AccountId), Balance>,
+}
+
+#[near_bindgen]
+impl Contract{
+    #[init]
+    pub fn new(owner_id: AccountId) -> Self{
+        assert!(!env::state_exists(), "Already initialized");
+
+        Self {
+            owner_id: owner_id,
+            pools: Vector::new(b"p"),
+            deposits: LookupMap::new(b"d"),
+        }
+    }
+
+    pub fn add_simple_pool(&mut self, tokens: Vec<AccountId>, fee: u32) -> u64{
+        assert_eq!(env::predecessor_account_id(), self.owner_id, "Only the owner can add pools");
+        assert_eq!(tokens.len(), 2, "A pool has two tokens");
+        assert!(fee < FEE_DIVISOR, "The fee should be less than 100%");
+
+        self.pools.push(&SimplePool { token_account_ids: tokens, amounts: vec![U128(0), U128(0)], total_fee: fee });
+        return self.pools.len() - 1;
+    }
+
+    /// Moves the deposits of the caller to the pool
+    pub fn add_liquidity(&mut self, pool_id: u64, amounts: Vec<U128>){
+        let account_id = env::predecessor_account_id();
+        let mut pool = self.get_pool(pool_id);
+        assert_eq!(amounts.len(), 2, "A pool has two tokens");
+
+        for idx in 0..2{
+            self.withdraw_deposit(&account_id, &pool.token_account_ids[idx], amounts[idx].0);
+            pool.amounts[idx] = U128(pool.amounts[idx].0 + amounts[idx].0);
+        }
+        self.pools.replace(pool_id, &pool);
+    }
+
+    pub fn get_pool(&self, pool_id: u64) -> SimplePool{
+        return self.pools.get(pool_id).expect("There is no such pool");
+    }
+
+    pub fn get_deposit(&self, account_id: AccountId, token_id: AccountId) -> U128{
+        return U128(self.deposits.get(&(account_id, token_id)).unwrap_or(0));
+    }
+
+    pub fn get_return(&self, pool_id: u64, token_in: AccountId, amount_in: U128, token_out: AccountId) -> U128{
+        return U128(get_return(&self.get_pool(pool_id), &token_in, amount_in.0, &token_out));
+    }
+
+    /// Swaps the deposits of the caller and returns the output of the last action
+    pub fn swap(&mut self, actions: Vec<SwapAction>, referral_id: Option<AccountId>) -> U128{
+        let _ = referral_id;
+        let account_id = env::predecessor_account_id();
+        let mut amount_out = 0;
+
+        for action in actions{
+            let amount_in = action.amount_in.map_or(amount_out, |el| el.0);
+            let mut pool = self.get_pool(action.pool_id);
+            amount_out = get_return(&pool, &action.token_in, amount_in, &action.token_out);
+            assert!(amount_out >= action.min_amount_out.0, "Slippage error, the output is {} but at least {} is expected", amount_out, action.min_amount_out.0);
+
+            self.withdraw_deposit(&account_id, &action.token_in, amount_in);
+            self.add_deposit(&account_id, &action.token_out, amount_out);
+
+            let in_idx = token_index(&pool, &action.token_in);
+            pool.amounts[in_idx] = U128(pool.amounts[in_idx].0 + amount_in);
+            pool.amounts[1 - in_idx] = U128(pool.amounts[1 - in_idx].0 - amount_out);
+            self.pools.replace(action.pool_id, &pool);
+
+            events::events::swap(&account_id, &action.token_in, amount_in, &action.token_out, amount_out);
+        }
+
+        return U128(amount_out);
+    }
+
+    /// Sends a deposit of the caller back, the deposit is restored if the transfer fails
+    #[payable]
+    pub fn withdraw(&mut self, token_id: AccountId, amount: U128, unregister: Option<bool>) -> Promise{
+        assert_one_yocto();
+        let _ = unregister;
+        let account_id = env::predecessor_account_id();
+        self.withdraw_deposit(&account_id, &token_id, amount.0);
+
+        return ext_fungible_token::ft_transfer(account_id.clone(), amount, None, token_id.clone(), 1, GAS_FOR_FT_TRANSFER)
+            .then(ext_self::exchange_callback_post_withdraw(token_id, account_id, amount, env::current_account_id(), 0, GAS_FOR_AFTER_FT_TRANSFER));
+    }
+
+    #[private]
+    pub fn exchange_callback_post_withdraw(&mut self, token_id: AccountId, sender_id: AccountId, amount: U128){
+        if let PromiseResult::Failed = env::promise_result(0){
+            self.add_deposit(&sender_id, &token_id, amount.0);
+        }
+    }
+
+    fn add_deposit(&mut self, account_id: &AccountId, token_id: &AccountId, amount: Balance){
+        let key = (account_id.clone(), token_id.clone());
+        let deposit = self.deposits.get(&key).unwrap_or(0);
+        self.deposits.insert(&key, &(deposit + amount));
+    }
+
+    fn withdraw_deposit(&mut self, account_id: &AccountId, token_id: &AccountId, amount: Balance){
+        let key = (account_id.clone(), token_id.clone());
+        let deposit = self.deposits.get(&key).unwrap_or(0);
+        assert!(deposit >= amount, "Not enough deposit of {}", token_id);
+        self.deposits.insert(&key, &(deposit - amount));
+    }
+}
+
+fn token_index(pool: &SimplePool, token_id: &AccountId) -> usize{
+    return pool.token_account_ids
+        .iter()
+        .position(|el| el == token_id)
+        .unwrap_or_else(|| panic!("The pool doesn't have {}", token_id));
+}
+
+/// The output of a swap of `amount_in` after the fee, with a constant product of the amounts
+fn get_return(pool: &SimplePool, token_in: &AccountId, amount_in: Balance, token_out: &AccountId) -> Balance{
+    let in_idx = token_index(pool, token_in);
+    assert_eq!(token_index(pool, token_out), 1 - in_idx, "The tokens should be different");
+
+    let amount_in = U256::from(amount_in) * U256::from(FEE_DIVISOR - pool.total_fee);
+    let in_balance = U256::from(pool.amounts[in_idx].0) * U256::from(FEE_DIVISOR);
+    let out_balance = U256::from(pool.amounts[1 - in_idx].0);
+    if in_balance + amount_in == U256::zero(){
+        return 0;
+    }
+
+    return (amount_in * out_balance / (in_balance + amount_in)).as_u128();
+}
+
+#[near_bindgen]
+impl FungibleTokenReceiver for Contract{
+    /// Every transfer is a deposit of the sender
+    fn ft_on_transfer(&mut self, sender_id: AccountId, amount: U128, msg: String) -> PromiseOrValue<U128> {
+        assert!(msg.is_empty(), "Only deposits are supported");
+        let token_id = env::predecessor_account_id();
+        self.add_deposit(&sender_id, &token_id, amount.0);
+
+        return PromiseOrValue::Value(U128(0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{testing_env, AccountId};
+
+    use crate::{Contract, SwapAction};
+
+    fn account(name: &str) -> AccountId{
+        AccountId::new_unchecked(name.to_string())
+    }
+
+    fn set_context(predecessor: AccountId){
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(account("dex.near"))
+            .predecessor_account_id(predecessor)
+            .attached_deposit(1)
+            .build());
+    }
+
+    fn setup() -> Contract{
+        set_context(account("owner.near"));
+        let mut contract = Contract::new(account("owner.near"));
+        contract.add_simple_pool(vec![account("reward.near"), account("usdc.near")], 30);
+
+        set_context(account("reward.near"));
+        contract.ft_on_transfer(account("owner.near"), U128(1000), "".to_string());
+        set_context(account("usdc.near"));
+        contract.ft_on_transfer(account("owner.near"), U128(2000), "".to_string());
+        set_context(account("owner.near"));
+        contract.add_liquidity(0, vec![U128(1000), U128(2000)]);
+
+        return contract;
+    }
+
+    fn swap_action(amount_in: u128, min_amount_out: u128) -> SwapAction{
+        return SwapAction {
+            pool_id: 0,
+            token_in: account("reward.near"),
+            amount_in: Some(U128(amount_in)),
+            token_out: account("usdc.near"),
+            min_amount_out: U128(min_amount_out),
+        };
+    }
+
+    #[test]
+    fn test_swap() {
+        let mut contract = setup();
+        set_context(account("reward.near"));
+        contract.ft_on_transfer(account("alice.near"), U128(100), "".to_string());
+
+        set_context(account("alice.near"));
+        assert_eq!(contract.get_return(0, account("reward.near"), U128(100), account("usdc.near")), U128(181));
+        assert_eq!(contract.swap(vec![swap_action(100, 180)], None), U128(181));
+        assert_eq!(contract.get_deposit(account("alice.near"), account("reward.near")), U128(0));
+        assert_eq!(contract.get_deposit(account("alice.near"), account("usdc.near")), U128(181));
+        assert_eq!(contract.get_pool(0).amounts, vec![U128(1100), U128(1819)]);
+
+        contract.withdraw(account("usdc.near"), U128(181), None);
+        assert_eq!(contract.get_deposit(account("alice.near"), account("usdc.near")), U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Slippage error")]
+    fn test_swap_min_amount_out() {
+        let mut contract = setup();
+        set_context(account("reward.near"));
+        contract.ft_on_transfer(account("alice.near"), U128(100), "".to_string());
+
+        set_context(account("alice.near"));
+        contract.swap(vec![swap_action(100, 182)], None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough deposit of reward.near")]
+    fn test_swap_without_deposit() {
+        let mut contract = setup();
+        set_context(account("alice.near"));
+        contract.swap(vec![swap_action(100, 0)], None);
+    }
+}
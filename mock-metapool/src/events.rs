@@ -0,0 +1,47 @@
+pub mod events{
+    use near_sdk::json_types::U128;
+    use near_sdk::{AccountId, Balance, log};
+    use near_sdk::serde::{Serialize};
+    use near_sdk::serde_json::json;
+
+    #[derive(Serialize)]
+    #[serde(crate = "near_sdk::serde")]
+    struct Event<'a> {
+        pub account_id: &'a AccountId,
+        pub amount: U128,
+        pub st_near: U128,
+    }
+
+    fn log_event<T: Serialize>(event: &str, data: T) {
+        let event = json!({
+            "standard": "mock-metapool",
+            "version": "1.0.0",
+            "event": event,
+            "data": [data]
+        });
+
+        log!("EVENT_JSON:{}", event.to_string());
+    }
+
+    pub fn stake(account_id: &AccountId, amount: Balance, st_near: Balance){
+        log_event(
+            "stake",
+            Event {
+                account_id: &account_id,
+                amount: U128(amount),
+                st_near: U128(st_near),
+            }
+        );
+    }
+
+    pub fn liquid_unstake(account_id: &AccountId, amount: Balance, st_near: Balance){
+        log_event(
+            "liquid_unstake",
+            Event {
+                account_id: &account_id,
+                amount: U128(amount),
+                st_near: U128(st_near),
+            }
+        );
+    }
+}
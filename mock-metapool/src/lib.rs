@@ -0,0 +1,212 @@
+use near_sdk::collections::UnorderedMap;
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Serialize, Deserialize};
+use near_sdk::{self, near_bindgen, log, env, AccountId, Balance, Promise, ONE_NEAR};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use common::types::U256;
+
+const BPS_NOMINAL: u128 = 10_000;
+const DEFAULT_LIQUID_UNSTAKE_FEE_BPS: u32 = 30;
+
+mod events;
+
+fn mul_div(a: u128, b: u128, c: u128) -> u128{
+    return (U256::from(a) * U256::from(b) / U256::from(c)).as_u128();
+}
+
+/// A simplified Meta Pool. The staked NEAR is never sent to validators,
+/// the staking rewards are added with `distribute_rewards`
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Contract {
+    /// stNEAR of every account
+    pub accounts: UnorderedMap<AccountId, Balance>,
+    pub total_st_near: Balance,
+    /// The NEAR backing all stNEAR
+    pub total_staked: Balance,
+    pub liquid_unstake_fee_bps: u32,
+}
+
+impl Default for Contract{
+    fn default() -> Self {
+        Self {
+            accounts: UnorderedMap::new(b"a"),
+            total_st_near: 0,
+            total_staked: 0,
+            liquid_unstake_fee_bps: DEFAULT_LIQUID_UNSTAKE_FEE_BPS,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LiquidUnstakeResult{
+    pub near: U128,
+    pub fee: U128,
+    pub meta: U128,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GetAccountInfoResult{
+    pub account_id: AccountId,
+    pub st_near: U128,
+    pub valued_st_near: U128,
+}
+
+#[near_bindgen]
+impl Contract{
+    #[init]
+    pub fn new() -> Self{
+        assert!(!env::state_exists(), "Already initialized");
+
+        Contract::default()
+    }
+
+    /// Price of one stNEAR in yoctoNEAR
+    pub fn get_st_near_price(&self) -> U128{
+        if self.total_st_near == 0{
+            return U128(ONE_NEAR);
+        }
+
+        return U128(mul_div(self.total_staked, ONE_NEAR, self.total_st_near));
+    }
+
+    pub fn get_account_info(&self, account_id: AccountId) -> GetAccountInfoResult{
+        let st_near = self.accounts.get(&account_id).unwrap_or(0);
+
+        return GetAccountInfoResult {
+            account_id: account_id,
+            st_near: U128(st_near),
+            valued_st_near: U128(self.st_near_to_near(st_near))
+        };
+    }
+
+    #[payable]
+    pub fn deposit_and_stake(&mut self){
+        let account_id = env::predecessor_account_id();
+        let amount = env::attached_deposit();
+        assert!(amount > 0, "Deposit should be a positive number");
+
+        let st_near = self.near_to_st_near(amount);
+        let balance = self.accounts.get(&account_id).unwrap_or(0);
+        self.accounts.insert(&account_id, &(balance + st_near));
+        self.total_st_near += st_near;
+        self.total_staked += amount;
+
+        events::events::stake(&account_id, amount, st_near);
+    }
+
+    /// Burns stNEAR and sends back its value in NEAR without the fee.
+    /// The fee stays staked, so it raises the price of stNEAR
+    pub fn liquid_unstake(&mut self, st_near_to_burn: U128, min_expected_near: U128) -> LiquidUnstakeResult{
+        let account_id = env::predecessor_account_id();
+        let balance = self.accounts.get(&account_id).unwrap_or(0);
+        assert!(st_near_to_burn.0 <= balance, "Not enough stNEAR");
+
+        let near = self.st_near_to_near(st_near_to_burn.0);
+        let fee = mul_div(near, u128::from(self.liquid_unstake_fee_bps), BPS_NOMINAL);
+        let near_to_send = near - fee;
+        assert!(near_to_send >= min_expected_near.0, "Price changed, the expected NEAR is {} but it is {}", min_expected_near.0, near_to_send);
+
+        self.accounts.insert(&account_id, &(balance - st_near_to_burn.0));
+        self.total_st_near -= st_near_to_burn.0;
+        self.total_staked -= near_to_send;
+
+        events::events::liquid_unstake(&account_id, near_to_send, st_near_to_burn.0);
+        Promise::new(account_id).transfer(near_to_send);
+
+        return LiquidUnstakeResult { near: U128(near_to_send), fee: U128(fee), meta: U128(0) };
+    }
+
+    /// Adds the attached NEAR as staking rewards to all stNEAR holders
+    #[payable]
+    pub fn distribute_rewards(&mut self){
+        assert!(self.total_st_near > 0, "There is no stNEAR to reward");
+        log!("Distributing {} NEAR rewards", env::attached_deposit());
+        self.total_staked += env::attached_deposit();
+    }
+
+    pub fn set_liquid_unstake_fee_bps(&mut self, fee_bps: u32){
+        assert_eq!(env::predecessor_account_id(), env::current_account_id(), "Only the contract can set the fee");
+        assert!(u128::from(fee_bps) < BPS_NOMINAL, "Fee should be less than 100%");
+        self.liquid_unstake_fee_bps = fee_bps;
+    }
+
+    fn near_to_st_near(&self, amount: Balance) -> Balance{
+        if self.total_staked == 0{
+            return amount;
+        }
+
+        return mul_div(amount, self.total_st_near, self.total_staked);
+    }
+
+    fn st_near_to_near(&self, st_near: Balance) -> Balance{
+        if self.total_st_near == 0{
+            return st_near;
+        }
+
+        return mul_div(st_near, self.total_staked, self.total_st_near);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{testing_env, AccountId, ONE_NEAR};
+
+    use crate::Contract;
+
+    fn alice() -> AccountId{
+        AccountId::new_unchecked("alice.near".to_string())
+    }
+
+    fn set_context(predecessor: AccountId, attached_deposit: u128){
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(AccountId::new_unchecked("metapool.near".to_string()))
+            .predecessor_account_id(predecessor)
+            .attached_deposit(attached_deposit)
+            .account_balance(1000 * ONE_NEAR)
+            .build());
+    }
+
+    #[test]
+    fn test_stake_and_rewards() {
+        set_context(alice(), 10 * ONE_NEAR);
+        let mut contract = Contract::new();
+        contract.deposit_and_stake();
+        assert_eq!(contract.get_account_info(alice()).st_near, U128(10 * ONE_NEAR));
+        assert_eq!(contract.get_st_near_price(), U128(ONE_NEAR));
+
+        contract.distribute_rewards();
+        assert_eq!(contract.get_st_near_price(), U128(2 * ONE_NEAR));
+        assert_eq!(contract.get_account_info(alice()).valued_st_near, U128(20 * ONE_NEAR));
+    }
+
+    #[test]
+    fn test_liquid_unstake() {
+        set_context(alice(), 10 * ONE_NEAR);
+        let mut contract = Contract::new();
+        contract.deposit_and_stake();
+
+        set_context(alice(), 0);
+        let result = contract.liquid_unstake(U128(5 * ONE_NEAR), U128(4 * ONE_NEAR));
+        // 0.3% fee
+        assert_eq!(result.fee, U128(15 * ONE_NEAR / 1000));
+        assert_eq!(result.near.0 + result.fee.0, 5 * ONE_NEAR);
+        assert_eq!(contract.get_account_info(alice()).st_near, U128(5 * ONE_NEAR));
+        assert!(contract.get_st_near_price().0 > ONE_NEAR);
+    }
+
+    #[test]
+    #[should_panic(expected = "Price changed")]
+    fn test_liquid_unstake_min_expected() {
+        set_context(alice(), 10 * ONE_NEAR);
+        let mut contract = Contract::new();
+        contract.deposit_and_stake();
+
+        set_context(alice(), 0);
+        contract.liquid_unstake(U128(5 * ONE_NEAR), U128(5 * ONE_NEAR));
+    }
+}
@@ -0,0 +1,99 @@
+use near_contract_standards::fungible_token::FungibleToken;
+use near_contract_standards::fungible_token::events::{FtMint, FtBurn};
+use near_sdk::json_types::U128;
+use near_sdk::{self, near_bindgen, env, assert_one_yocto, AccountId, Balance, Promise, PromiseOrValue};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+
+/// A simplified wrap.near. The attached NEAR is wrapped 1:1 to wNEAR,
+/// unwrapping burns the wNEAR and sends the NEAR back
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct Contract {
+    pub token: FungibleToken,
+}
+
+impl Default for Contract{
+    fn default() -> Self {
+        Self {
+            token: FungibleToken::new(b"t".to_vec()),
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract{
+    #[init]
+    pub fn new() -> Self{
+        assert!(!env::state_exists(), "Already initialized");
+
+        Contract::default()
+    }
+
+    /// Unlike wrap.near, the caller is registered if it isn't yet
+    #[payable]
+    pub fn near_deposit(&mut self){
+        let account_id = env::predecessor_account_id();
+        let amount = env::attached_deposit();
+        assert!(amount > 0, "Deposit should be a positive number");
+
+        if !self.token.accounts.contains_key(&account_id){
+            self.token.internal_register_account(&account_id);
+        }
+        self.token.internal_deposit(&account_id, amount);
+        FtMint { owner_id: &account_id, amount: &U128(amount), memo: Some("Wrap") }.emit();
+    }
+
+    #[payable]
+    pub fn near_withdraw(&mut self, amount: U128) -> Promise{
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+
+        self.token.internal_withdraw(&account_id, amount.0);
+        FtBurn { owner_id: &account_id, amount: &amount, memo: Some("Unwrap") }.emit();
+
+        return Promise::new(account_id).transfer(amount.0);
+    }
+
+    fn on_account_closed(&mut self, account_id: AccountId, balance: Balance){
+        near_sdk::log!("Closed @{} with {}", account_id, balance);
+    }
+}
+
+near_contract_standards::impl_fungible_token_core!(Contract, token);
+near_contract_standards::impl_fungible_token_storage!(Contract, token, on_account_closed);
+
+#[cfg(test)]
+mod tests {
+    use near_contract_standards::fungible_token::core::FungibleTokenCore;
+    use near_sdk::json_types::U128;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{testing_env, AccountId, ONE_NEAR};
+
+    use crate::Contract;
+
+    fn alice() -> AccountId{
+        AccountId::new_unchecked("alice.near".to_string())
+    }
+
+    fn set_context(predecessor: AccountId, attached_deposit: u128){
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(AccountId::new_unchecked("wnear.near".to_string()))
+            .predecessor_account_id(predecessor)
+            .attached_deposit(attached_deposit)
+            .account_balance(1000 * ONE_NEAR)
+            .build());
+    }
+
+    #[test]
+    fn test_wrap_and_unwrap() {
+        set_context(alice(), 10 * ONE_NEAR);
+        let mut contract = Contract::new();
+        contract.near_deposit();
+        assert_eq!(contract.ft_balance_of(alice()), U128(10 * ONE_NEAR));
+
+        set_context(alice(), 1);
+        contract.near_withdraw(U128(4 * ONE_NEAR));
+        assert_eq!(contract.ft_balance_of(alice()), U128(6 * ONE_NEAR));
+        assert_eq!(contract.ft_total_supply(), U128(6 * ONE_NEAR));
+    }
+}
@@ -0,0 +1,59 @@
+use crate::*;
+use common::access_control::ContractRole;
+use common::impl_access_control;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Role{
+    /// Manages keepers and pausers and the configuration of the pool
+    Admin,
+    /// Runs the periodic operations, like adding prize distributions
+    Keeper,
+    /// Can pause the pool in case of emergency
+    Pauser,
+}
+
+impl ContractRole for Role{
+    fn admin() -> Self{
+        return Role::Admin;
+    }
+}
+
+impl_access_control!(Contract, access_control, Role, "pool-together");
+
+#[cfg(test)]
+mod tests{
+    use crate::access_control::Role;
+    use crate::test_utils::{get_contract, mmmm, sec, burrow, set_caller};
+
+    #[test]
+    fn test_roles(){
+        let mut contract = get_contract();
+        assert!(contract.has_role(Role::Keeper, mmmm()));
+        assert!(!contract.has_role(Role::Keeper, sec()));
+
+        set_caller(mmmm());
+        contract.grant_role(Role::Admin, sec());
+        set_caller(sec());
+        contract.grant_role(Role::Keeper, burrow());
+        assert_eq!(contract.get_roles(burrow()), vec![Role::Keeper]);
+        assert!(!contract.has_role(Role::Pauser, burrow()));
+
+        contract.revoke_role(Role::Keeper, burrow());
+        assert!(contract.get_roles(burrow()).is_empty());
+    }
+
+    #[test]
+    fn test_two_step_ownership_transfer(){
+        let mut contract = get_contract();
+        set_caller(mmmm());
+        contract.transfer_ownership(sec());
+        assert_eq!(contract.get_owner(), mmmm());
+        assert_eq!(contract.get_pending_owner(), Some(sec()));
+
+        set_caller(sec());
+        contract.accept_ownership();
+        assert_eq!(contract.get_owner(), sec());
+        assert_eq!(contract.get_pending_owner(), None);
+    }
+}
@@ -0,0 +1,187 @@
+use near_sdk::collections::LookupMap;
+use common::types::{DrawId, U256};
+use utils::storage_keys::StorageKeys;
+use crate::events::events;
+use crate::*;
+
+/// Splits the pool position in the yield source to the deposits of the accounts and the prizes
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct PoolAccounting{
+    /// The deposits of the accounts, they can always be withdrawn
+    pub total_principal: Balance,
+    pub total_yield_harvested: Balance,
+    /// The yield harvested when the prize distribution of the draw was added
+    pub yield_harvested: LookupMap<DrawId, Balance>,
+    pub prizes_paid: Balance,
+    /// Unclaimed prizes that are added to the next prize distribution
+    pub rollover: Balance,
+    /// Unclaimed prizes that are kept aside
+    pub reserve: Balance,
+    /// The yield in the yield sources was earned after this time, in ms
+    pub last_harvested_at: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PoolAccountingView{
+    pub total_principal: U128,
+    pub total_yield_harvested: U128,
+    pub prizes_paid: U128,
+    pub rollover: U128,
+    pub reserve: U128,
+}
+
+impl Default for PoolAccounting{
+    fn default() -> Self {
+        PoolAccounting {
+            total_principal: 0,
+            total_yield_harvested: 0,
+            yield_harvested: LookupMap::new(StorageKeys::YieldHarvested),
+            prizes_paid: 0,
+            rollover: 0,
+            reserve: 0,
+            last_harvested_at: env::block_timestamp_ms(),
+        }
+    }
+}
+
+impl PoolAccounting{
+    pub fn on_deposit(&mut self, amount: Balance){
+        self.total_principal += amount;
+    }
+
+    pub fn on_withdraw(&mut self, amount: Balance){
+        assert!(amount <= self.total_principal, "Withdrawing more than the total principal");
+        self.total_principal -= amount;
+    }
+
+    /// The yield after `harvested_until` is left for the next draws
+    pub fn on_harvest(&mut self, draw_id: DrawId, amount: Balance, harvested_until: u64){
+        self.total_yield_harvested += amount;
+        self.yield_harvested.insert(&draw_id, &amount);
+        self.last_harvested_at = self.last_harvested_at.max(harvested_until);
+    }
+
+    /// The part of the yield earned since the last harvest until `current_time` that was earned 
+    /// between `started_at` and `completed_at`, as if the yield was earned evenly over the time
+    pub fn yield_for_period(&self, unharvested_yield: Balance, started_at: u64, completed_at: u64, current_time: u64) -> Balance{
+        let from = self.last_harvested_at.max(started_at);
+        let to = completed_at.min(current_time);
+        if to <= from{
+            return 0;
+        }
+
+        return (U256::from(unharvested_yield) * U256::from(to - from) / U256::from(current_time - self.last_harvested_at)).as_u128();
+    }
+
+    pub fn on_prize_paid(&mut self, amount: Balance){
+        self.prizes_paid += amount;
+    }
+
+    pub fn on_prize_payment_reverted(&mut self, amount: Balance){
+        self.prizes_paid -= amount;
+    }
+}
+
+#[near_bindgen]
+impl Contract{
+    pub fn get_pool_accounting(&self) -> PoolAccountingView{
+        return PoolAccountingView {
+            total_principal: U128(self.accounting.total_principal),
+            total_yield_harvested: U128(self.accounting.total_yield_harvested),
+            prizes_paid: U128(self.accounting.prizes_paid),
+            rollover: U128(self.accounting.rollover),
+            reserve: U128(self.accounting.reserve),
+        };
+    }
+
+    pub fn get_yield_harvested(&self, draw_id: U128) -> U128{
+        return U128(self.accounting.yield_harvested.get(&draw_id.0).unwrap_or(0));
+    }
+
+    /// Checks that the pool position in the yield sources covers the principal
+    pub fn check_pool_accounting(&self) -> Promise{
+        return self
+            .get_yield_sources_balance()
+            .then(this_contract::on_check_pool_accounting(env::current_account_id(), 0, gas::GET_BALANCE_FROM_DEFI));
+    }
+
+    #[private]
+    pub fn on_check_pool_accounting(&self) -> bool{
+        let balance = self.get_yield_sources_balance_result()
+            .unwrap_or_else(|| panic!("Error when getting the balance from the yield sources"));
+
+        return self.is_principal_covered(balance);
+    }
+
+    /// Returns the part of the pool position in the yield source that is neither principal nor committed to prizes
+    pub (crate) fn get_reward_for_position(&self, position: Balance) -> Balance{
+        if !self.is_principal_covered(position){
+            return 0;
+        }
+
+        let committed = self.accounting.total_principal + self.get_committed_prizes();
+        if position < committed{
+            log!("The position in the yield source {} is less than the principal and prizes {}", position, committed);
+            return 0;
+        }
+
+        return position - committed;
+    }
+
+    pub (crate) fn is_principal_covered(&self, yield_source_balance: Balance) -> bool{
+        if self.accounting.total_principal > yield_source_balance{
+            events::principal_shortfall(self.accounting.total_principal, yield_source_balance);
+            return false;
+        }
+
+        return true;
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use crate::*;
+    use crate::test_utils::get_contract;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::PoolAccountingView;
+
+    #[test]
+    fn test_pool_accounting(){
+        testing_env!(VMContextBuilder::new().build());
+        let mut contract = get_contract();
+
+        contract.accounting.on_deposit(100);
+        contract.accounting.on_deposit(50);
+        contract.accounting.on_withdraw(30);
+        contract.accounting.on_harvest(1, 20, 10);
+        contract.accounting.on_prize_paid(15);
+        contract.accounting.on_prize_paid(5);
+        contract.accounting.on_prize_payment_reverted(5);
+
+        assert_eq!(contract.get_pool_accounting(), PoolAccountingView {
+            total_principal: U128(120),
+            total_yield_harvested: U128(20),
+            prizes_paid: U128(15),
+            rollover: U128(0),
+            reserve: U128(0),
+        });
+        assert_eq!(contract.get_yield_harvested(U128(1)), U128(20));
+        assert_eq!(contract.get_yield_harvested(U128(2)), U128(0));
+
+        assert!(contract.is_principal_covered(120));
+        assert!(!contract.is_principal_covered(119));
+    }
+
+    #[test]
+    #[should_panic(expected = "Withdrawing more than the total principal")]
+    fn test_withdraw_more_than_principal(){
+        testing_env!(VMContextBuilder::new().build());
+        let mut contract = get_contract();
+
+        contract.accounting.on_deposit(100);
+        contract.accounting.on_withdraw(101);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{*, utils::gas::{GAS_FOR_AFTER_FT_TRANSFER, GAS_FOR_FT_TRANSFER}};
+use crate::*;
 use near_sdk::{AccountId, Balance, json_types::{U128}, PromiseError, Promise};
 use crate::interfaces::defi::IYieldSource;
 use near_sdk::serde::{Deserialize};
@@ -33,22 +33,39 @@ pub (crate) struct BurrowYieldSource{
     pub(crate) address: AccountId,
 }
 
+impl BurrowYieldSource{
+    fn source(&self) -> YieldSource{
+        return YieldSource::Burrow { address: self.address.clone() };
+    }
+}
+
 #[near_bindgen]
 impl Contract{
+    fn get_position_in_defi(&self, amounts: &Vec<TokenAmountsView>) -> Balance{
+        return amounts
+            .iter()
+            .find(|el| el.token == self.deposited_token_id)
+            .map_or(0, |el| el.shares.0 + el.rewards.0);
+    }
+
+    /// Returns the part of the pool position in defi that is neither principal nor committed to prizes
     #[private]
     pub fn on_get_reward_from_defi(&self, #[callback_result] call_result: Result<Vec<TokenAmountsView>, PromiseError>)-> Balance{
-        return 10;
+        if call_result.is_err(){
+            panic!("Error when getting the reward from defi");
+        }
+
+        return self.get_reward_for_position(self.get_position_in_defi(&call_result.unwrap()));
     }
 
+    /// Returns the whole pool position in defi
     #[private]
-    pub fn on_after_rewards_claim_from_defi(&mut self, account_id: AccountId, amount: Balance, #[callback_result] result: Result<(), PromiseError>){
-        if result.is_err(){
-            log!("Error when claiming rewards from defi");
-            return;
+    pub fn on_get_balance_from_defi(&self, #[callback_result] call_result: Result<Vec<TokenAmountsView>, PromiseError>)-> Balance{
+        if call_result.is_err(){
+            panic!("Error when getting the balance from defi");
         }
 
-        // Send to user account
-        ext_fungible_token::ft_transfer(account_id, U128(amount), None, self.deposited_token_id.clone(), 1, GAS_FOR_FT_TRANSFER);
+        return self.get_position_in_defi(&call_result.unwrap());
     }
 }
 
@@ -59,22 +76,61 @@ impl IYieldSource for BurrowYieldSource{
          .then(crate::this_contract::on_get_reward_from_defi(env::current_account_id(), 0, gas::GET_BALANCE_FROM_DEFI))
     }
 
-    fn transfer(&self, token_id: &AccountId, amount: Balance) {
+    fn get_balance(&self, account_id: &AccountId) -> Promise {
+        ext_defi::show_reward(account_id.clone(), self.address.clone(), 0, gas::GAS_FOR_FT_TRANSFER)
+         .then(crate::this_contract::on_get_balance_from_defi(env::current_account_id(), 0, gas::GET_BALANCE_FROM_DEFI))
+    }
+
+    fn transfer(&self, token_id: &AccountId, amount: Balance) -> Promise {
         log!("Transfer to {} {}", self.address, token_id.clone());
         log!("burrow transfer prepaid:{} used: {}", env::prepaid_gas().0, env::used_gas().0);
 
-        ext_fungible_token::ft_transfer_call(self.address.clone(), amount.into(), None, "".to_string(), token_id.clone(), 1, gas::GAS_FOR_TRANSFER_TO_DEFI);
+        ext_fungible_token::ft_transfer_call(self.address.clone(), amount.into(), None, "".to_string(), token_id.clone(), 1, gas::GAS_FOR_TRANSFER_TO_DEFI)
     }
 
-
-    fn claim(&self, account_id: &AccountId, token_id: &AccountId, amount: Balance) {
+    fn redeem(&self, token_id: &AccountId, amount: Balance) -> Promise {
         let asset_amount = AssetAmount{ token_id: token_id.clone(), amount: Some(U128(amount)), max_amount: None};
         let action = Action::Withdraw(
             asset_amount
         );
 
-        ext_defi::execute(vec![action], self.address.clone(), 1, gas::MAX_GAS)
-        .then(crate::this_contract::on_after_rewards_claim_from_defi(account_id.clone(), amount, token_id.clone(), 1, gas::GAS_FOR_FT_TRANSFER));
+        ext_defi::execute(vec![action], self.address.clone(), 1, gas::GAS_FOR_WITHDRAW_FROM_DEFI)
+    }
+
+    fn withdraw(&self, account_id: &AccountId, token_id: &AccountId, amount: Balance) -> Promise {
+        self.redeem(token_id, amount)
+        .then(crate::this_contract::on_withdraw_from_defi(account_id.clone(), amount, self.source(), env::current_account_id(), 0, gas::GAS_FOR_AFTER_WITHDRAW_FROM_DEFI))
     }
     
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests{
+    use crate::*;
+    use crate::test_utils::{get_contract, mmmm, usdc, position};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn test_reward_from_defi(){
+        let mut contract = get_contract();
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(mmmm())
+            .predecessor_account_id(mmmm())
+            .build());
+        contract.mint_tokens(mmmm(), 100);
+        contract.accounting.on_deposit(100);
+
+        let other_token = AccountId::new_unchecked("other".to_string());
+        let reward = contract.on_get_reward_from_defi(Ok(vec![position(other_token, 1000, 1000), position(usdc(), 100, 30)]));
+        assert_eq!(reward, 30);
+
+        // the prizes that are not claimed yet are not yield
+        contract.accounting.reserve = 20;
+        let reward = contract.on_get_reward_from_defi(Ok(vec![position(usdc(), 100, 30)]));
+        assert_eq!(reward, 10);
+
+        let reward = contract.on_get_reward_from_defi(Ok(vec![position(usdc(), 90, 0)]));
+        assert_eq!(reward, 0);
+    }
+}
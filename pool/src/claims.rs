@@ -0,0 +1,224 @@
+use near_sdk::collections::UnorderedMap;
+use common::types::{DrawId, NumPicks};
+use utils::storage_keys::StorageKeys;
+use crate::*;
+
+#[derive(BorshDeserialize, BorshSerialize, Default)]
+pub struct DrawClaim{
+    pub picks: Vec<NumPicks>,
+    pub amount: Balance,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct DrawClaims{
+    draws: UnorderedMap<DrawId, DrawClaim>,
+}
+
+/// Keeps the picks that were already paid out for every account and draw
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct AccountsClaims{
+    accounts: UnorderedMap<AccountId, DrawClaims>
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ClaimView{
+    pub draw_id: U128,
+    pub picks: Vec<U128>,
+    pub amount: U128,
+}
+
+impl Default for AccountsClaims{
+    fn default() -> Self {
+        AccountsClaims { accounts: UnorderedMap::new(StorageKeys::AccountClaims) }
+    }
+}
+
+impl AccountsClaims{
+    fn get_draws(&self, account_id: &AccountId) -> DrawClaims{
+        return self.accounts.get(&account_id).unwrap_or_else(|| {
+            DrawClaims {
+                draws: UnorderedMap::new(
+                    StorageKeys::AccountDrawClaims {
+                        account_hash: utils::utils::get_hash(&account_id)
+                    }
+                )
+            }
+        });
+    }
+
+    pub fn is_claimed(&self, account_id: &AccountId, draw_id: &DrawId, pick: &NumPicks) -> bool{
+        return self.get_draws(&account_id).draws
+            .get(&draw_id)
+            .map_or(false, |el| el.picks.contains(&pick));
+    }
+
+    /// Records the pick as claimed, panics if it was claimed before
+    pub fn add_claim(&mut self, account_id: &AccountId, draw_id: &DrawId, pick: NumPicks, amount: Balance){
+        let mut acc_draws_claims = self.get_draws(&account_id);
+        let mut draw_claim = acc_draws_claims.draws.get(&draw_id).unwrap_or_default();
+        assert!(!draw_claim.picks.contains(&pick), "The pick {} is already claimed for draw {}", pick, draw_id);
+
+        draw_claim.picks.push(pick);
+        draw_claim.amount += amount;
+        acc_draws_claims.draws.insert(&draw_id, &draw_claim);
+        self.accounts.insert(&account_id, &acc_draws_claims);
+    }
+
+    /// Reverts a claim whose payout failed, so the pick can be claimed again
+    pub fn remove_claim(&mut self, account_id: &AccountId, draw_id: &DrawId, pick: NumPicks, amount: Balance){
+        let mut acc_draws_claims = self.get_draws(&account_id);
+        let mut draw_claim = match acc_draws_claims.draws.get(&draw_id){
+            Some(draw_claim) => draw_claim,
+            None => return,
+        };
+
+        draw_claim.picks.retain(|el| *el != pick);
+        draw_claim.amount -= amount;
+        if draw_claim.picks.is_empty(){
+            acc_draws_claims.draws.remove(&draw_id);
+        }else{
+            acc_draws_claims.draws.insert(&draw_id, &draw_claim);
+        }
+        self.accounts.insert(&account_id, &acc_draws_claims);
+    }
+
+    pub fn get_claims(&self, account_id: &AccountId) -> Vec<ClaimView>{
+        return self.get_draws(&account_id).draws
+            .iter()
+            .map(|(draw_id, draw_claim)| ClaimView {
+                draw_id: U128(draw_id),
+                picks: draw_claim.picks.iter().map(|el| U128(*el)).collect(),
+                amount: U128(draw_claim.amount),
+            })
+            .collect();
+    }
+}
+
+#[near_bindgen]
+impl Contract{
+    /// Returns the already claimed picks and the paid amount for every draw of the account
+    pub fn get_claims(&self, account_id: AccountId) -> Vec<ClaimView>{
+        return self.claims.get_claims(&account_id);
+    }
+
+    pub fn is_claimed(&self, account_id: AccountId, draw_id: U128, pick: U128) -> bool{
+        return self.claims.is_claimed(&account_id, &draw_id.0, &pick.0);
+    }
+
+    /// The prize was redeemed from the yield sources to the pool, it is sent to the winner.
+    /// The redeemed tokens above the prize stay idle, the missing ones are taken from the idle tokens.
+    /// If any redeem fails, the tokens of the others stay idle
+    #[private]
+    pub fn on_prize_redeemed(&mut self, account_id: AccountId, claimed: Vec<(DrawId, NumPicks, Balance)>, redeems: Vec<(YieldSource, Balance)>){
+        self.yield_sources.end_redeem();
+        let amount: Balance = claimed.iter().map(|(_, _, amount)| amount).sum();
+        let mut redeemed = 0;
+        let mut failed = false;
+        for (idx, (source, source_amount)) in redeems.iter().enumerate(){
+            match utils::utils::redeemed_amount_at(idx as u64, *source_amount){
+                Some(source_redeemed) => redeemed += source_redeemed,
+                None => {
+                    failed = true;
+                    self.yield_sources.restore_unpaid_yield(source, *source_amount);
+                },
+            }
+        }
+
+        if failed{
+            log!("Error when redeeming the prize for {} picks of @{}", claimed.len(), account_id);
+            self.revert_claims(&account_id, &claimed);
+            self.yield_sources.idle += redeemed;
+            return;
+        }
+
+        if redeemed >= amount{
+            self.yield_sources.idle += redeemed - amount;
+        } else if !self.yield_sources.take_idle(amount - redeemed){
+            log!("Only {} of the {} prize of @{} is redeemed", redeemed, amount, account_id);
+            self.revert_claims(&account_id, &claimed);
+            self.yield_sources.idle += redeemed;
+            return;
+        }
+
+        self.transfer_prize(account_id, claimed);
+    }
+
+    #[private]
+    pub fn on_after_claim(&mut self, account_id: AccountId, claimed: Vec<(DrawId, NumPicks, Balance)>, #[callback_result] result: Result<(), PromiseError>){
+        if result.is_err(){
+            log!("Error when paying the prize for {} picks to @{}", claimed.len(), account_id);
+            let amount = self.revert_claims(&account_id, &claimed);
+            // the tokens are already out of the yield source
+            self.yield_sources.idle += amount;
+        }
+    }
+}
+
+impl Contract{
+    /// Sends the prize held by the pool to the winner
+    pub (crate) fn transfer_prize(&mut self, account_id: AccountId, claimed: Vec<(DrawId, NumPicks, Balance)>) -> Promise{
+        let amount: Balance = claimed.iter().map(|(_, _, amount)| amount).sum();
+
+        return ext_fungible_token::ft_transfer(account_id.clone(), U128(amount), None, self.deposited_token_id.clone(), 1, gas::GAS_FOR_FT_TRANSFER)
+            .then(this_contract::on_after_claim(account_id, claimed, env::current_account_id(), 0, gas::GAS_FOR_AFTER_FT_TRANSFER));
+    }
+
+    /// The picks of a failed payout can be claimed again. Returns the reverted amount
+    fn revert_claims(&mut self, account_id: &AccountId, claimed: &Vec<(DrawId, NumPicks, Balance)>) -> Balance{
+        let mut total = 0;
+        for (draw_id, pick, amount) in claimed.iter(){
+            self.claims.remove_claim(account_id, draw_id, *pick, *amount);
+            self.on_claim_reverted(*draw_id, *amount);
+            total += amount;
+        }
+
+        return total;
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use crate::*;
+    use crate::test_utils::{mmmm, sec};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::{AccountsClaims, ClaimView};
+
+    #[test]
+    fn test_claims_ledger(){
+        testing_env!(VMContextBuilder::new().build());
+        let mut claims = AccountsClaims::default();
+
+        claims.add_claim(&mmmm(), &1, 3, 100);
+        claims.add_claim(&mmmm(), &1, 5, 20);
+        claims.add_claim(&sec(), &1, 3, 7);
+
+        assert!(claims.is_claimed(&mmmm(), &1, &3));
+        assert!(!claims.is_claimed(&mmmm(), &1, &4));
+        assert!(!claims.is_claimed(&mmmm(), &2, &3));
+        assert_eq!(
+            claims.get_claims(&mmmm()),
+            vec![ClaimView { draw_id: U128(1), picks: vec![U128(3), U128(5)], amount: U128(120) }]
+        );
+
+        claims.remove_claim(&mmmm(), &1, 3, 100);
+        assert!(!claims.is_claimed(&mmmm(), &1, &3));
+        assert_eq!(claims.get_claims(&mmmm())[0].amount, U128(20));
+
+        claims.remove_claim(&mmmm(), &1, 5, 20);
+        assert!(claims.get_claims(&mmmm()).is_empty());
+        assert!(claims.is_claimed(&sec(), &1, &3));
+    }
+
+    #[test]
+    #[should_panic(expected = "The pick 3 is already claimed for draw 1")]
+    fn test_double_claim(){
+        testing_env!(VMContextBuilder::new().build());
+        let mut claims = AccountsClaims::default();
+
+        claims.add_claim(&mmmm(), &1, 3, 100);
+        claims.add_claim(&mmmm(), &1, 3, 100);
+    }
+}
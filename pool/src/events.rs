@@ -0,0 +1,291 @@
+pub mod events{
+    use near_sdk::{AccountId, log};
+    use near_sdk::serde::{Serialize};
+    use near_sdk::serde_json::json;
+    use near_sdk::json_types::U128;
+    use near_sdk::Balance;
+    use common::types::DrawId;
+
+    use crate::pause::Operation;
+    use crate::interfaces::prize_distribution::PrizeConfig;
+    use crate::interfaces::defi::YieldSource;
+
+    #[derive(Serialize)]
+    #[serde(crate = "near_sdk::serde")]
+    struct DelegateChangedEvent<'a> {
+        pub account_id: &'a AccountId,
+        pub old_delegate_id: &'a AccountId,
+        pub new_delegate_id: &'a AccountId,
+    }
+
+    #[derive(Serialize)]
+    #[serde(crate = "near_sdk::serde")]
+    struct PauseEvent<'a> {
+        pub operation: Operation,
+        pub sender_id: &'a AccountId,
+    }
+
+    #[derive(Serialize)]
+    #[serde(crate = "near_sdk::serde")]
+    struct PrizeConfigEvent<'a> {
+        pub old_config: &'a PrizeConfig,
+        pub new_config: &'a PrizeConfig,
+        pub sender_id: &'a AccountId,
+    }
+
+    #[derive(Serialize)]
+    #[serde(crate = "near_sdk::serde")]
+    struct UnclaimedPrizeEvent {
+        pub draw_id: U128,
+        pub amount: U128,
+    }
+
+    #[derive(Serialize)]
+    #[serde(crate = "near_sdk::serde")]
+    struct PrincipalShortfallEvent {
+        pub total_principal: U128,
+        pub yield_source_balance: U128,
+    }
+
+    #[derive(Serialize)]
+    #[serde(crate = "near_sdk::serde")]
+    struct YieldSourceMigrationEvent<'a> {
+        pub old_source: &'a YieldSource,
+        pub new_source: &'a YieldSource,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub amount: Option<U128>,
+    }
+
+    #[derive(Serialize)]
+    #[serde(crate = "near_sdk::serde")]
+    struct MigrationDepositFailedEvent {
+        pub remaining: U128,
+    }
+
+    #[derive(Serialize)]
+    #[serde(crate = "near_sdk::serde")]
+    struct YieldSourceWeightEvent<'a> {
+        pub source: &'a YieldSource,
+        pub old_weight: u32,
+        pub new_weight: u32,
+    }
+
+    #[derive(Serialize)]
+    #[serde(crate = "near_sdk::serde")]
+    struct YieldSourceEvent<'a> {
+        pub source: &'a YieldSource,
+    }
+
+    #[derive(Serialize)]
+    #[serde(crate = "near_sdk::serde")]
+    struct RebalanceEvent<'a> {
+        /// `None` is the idle buffer of the pool
+        pub from: Option<&'a YieldSource>,
+        pub to: Option<&'a YieldSource>,
+        pub amount: U128,
+    }
+
+    #[derive(Serialize)]
+    #[serde(crate = "near_sdk::serde")]
+    struct IdleBufferEvent {
+        pub old_idle_buffer_bps: u32,
+        pub new_idle_buffer_bps: u32,
+    }
+
+    #[derive(Serialize)]
+    #[serde(crate = "near_sdk::serde")]
+    struct FarmRewardsEvent<'a> {
+        pub token_id: &'a AccountId,
+        pub amount: U128,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub amount_out: Option<U128>,
+    }
+
+    fn log_event<T: Serialize>(event: &str, data: T) {
+        let event = json!({
+            "standard": "pool-together",
+            "version": "1.0.0",
+            "event": event,
+            "data": [data]
+        });
+
+        log!("EVENT_JSON:{}", event.to_string());
+    }
+
+    pub fn delegate_changed(account_id: &AccountId, old_delegate_id: &AccountId, new_delegate_id: &AccountId){
+        log_event(
+            "delegate_changed", 
+            DelegateChangedEvent {
+                account_id: &account_id,
+                old_delegate_id: &old_delegate_id,
+                new_delegate_id: &new_delegate_id,
+            }
+        );
+    }
+
+    pub fn paused(operation: Operation, sender_id: &AccountId){
+        log_event(
+            "paused", 
+            PauseEvent {
+                operation: operation,
+                sender_id: &sender_id,
+            }
+        );
+    }
+
+    pub fn unpaused(operation: Operation, sender_id: &AccountId){
+        log_event(
+            "unpaused", 
+            PauseEvent {
+                operation: operation,
+                sender_id: &sender_id,
+            }
+        );
+    }
+
+    pub fn prize_config_updated(old_config: &PrizeConfig, new_config: &PrizeConfig, sender_id: &AccountId){
+        log_event(
+            "prize_config_updated", 
+            PrizeConfigEvent {
+                old_config: &old_config,
+                new_config: &new_config,
+                sender_id: &sender_id,
+            }
+        );
+    }
+
+    pub fn unclaimed_prize_rolled_over(draw_id: DrawId, amount: Balance){
+        log_event(
+            "unclaimed_prize_rolled_over", 
+            UnclaimedPrizeEvent {
+                draw_id: U128(draw_id),
+                amount: U128(amount),
+            }
+        );
+    }
+
+    pub fn unclaimed_prize_reserved(draw_id: DrawId, amount: Balance){
+        log_event(
+            "unclaimed_prize_reserved", 
+            UnclaimedPrizeEvent {
+                draw_id: U128(draw_id),
+                amount: U128(amount),
+            }
+        );
+    }
+
+    pub fn principal_shortfall(total_principal: Balance, yield_source_balance: Balance){
+        log_event(
+            "principal_shortfall", 
+            PrincipalShortfallEvent {
+                total_principal: U128(total_principal),
+                yield_source_balance: U128(yield_source_balance),
+            }
+        );
+    }
+
+    pub fn yield_source_migration_started(old_source: &YieldSource, new_source: &YieldSource){
+        log_event(
+            "yield_source_migration_started", 
+            YieldSourceMigrationEvent {
+                old_source: &old_source,
+                new_source: &new_source,
+                amount: None,
+            }
+        );
+    }
+
+    pub fn yield_source_migration_aborted(old_source: &YieldSource, new_source: &YieldSource){
+        log_event(
+            "yield_source_migration_aborted", 
+            YieldSourceMigrationEvent {
+                old_source: &old_source,
+                new_source: &new_source,
+                amount: None,
+            }
+        );
+    }
+
+    pub fn yield_source_migration_completed(old_source: &YieldSource, new_source: &YieldSource, amount: Balance){
+        log_event(
+            "yield_source_migration_completed", 
+            YieldSourceMigrationEvent {
+                old_source: &old_source,
+                new_source: &new_source,
+                amount: Some(U128(amount)),
+            }
+        );
+    }
+
+    pub fn yield_source_migration_deposit_failed(remaining: Balance){
+        log_event(
+            "yield_source_migration_deposit_failed", 
+            MigrationDepositFailedEvent {
+                remaining: U128(remaining),
+            }
+        );
+    }
+
+    pub fn yield_source_weight_updated(source: &YieldSource, old_weight: u32, new_weight: u32){
+        log_event(
+            "yield_source_weight_updated", 
+            YieldSourceWeightEvent {
+                source: &source,
+                old_weight: old_weight,
+                new_weight: new_weight,
+            }
+        );
+    }
+
+    pub fn yield_source_removed(source: &YieldSource){
+        log_event(
+            "yield_source_removed", 
+            YieldSourceEvent {
+                source: &source,
+            }
+        );
+    }
+
+    pub fn yield_sources_rebalanced(from: Option<&YieldSource>, to: Option<&YieldSource>, amount: Balance){
+        log_event(
+            "yield_sources_rebalanced", 
+            RebalanceEvent {
+                from: from,
+                to: to,
+                amount: U128(amount),
+            }
+        );
+    }
+
+    pub fn idle_buffer_updated(old_idle_buffer_bps: u32, new_idle_buffer_bps: u32){
+        log_event(
+            "idle_buffer_updated", 
+            IdleBufferEvent {
+                old_idle_buffer_bps: old_idle_buffer_bps,
+                new_idle_buffer_bps: new_idle_buffer_bps,
+            }
+        );
+    }
+
+    pub fn farm_rewards_harvested(token_id: &AccountId, amount: Balance){
+        log_event(
+            "farm_rewards_harvested", 
+            FarmRewardsEvent {
+                token_id: &token_id,
+                amount: U128(amount),
+                amount_out: None,
+            }
+        );
+    }
+
+    pub fn farm_rewards_swapped(token_id: &AccountId, amount: Balance, amount_out: Balance){
+        log_event(
+            "farm_rewards_swapped", 
+            FarmRewardsEvent {
+                token_id: &token_id,
+                amount: U128(amount),
+                amount_out: Some(U128(amount_out)),
+            }
+        );
+    }
+}
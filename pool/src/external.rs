@@ -1,14 +1,43 @@
 use near_sdk::Promise;
 
 use crate::*;
+use crate::metapool::{LiquidUnstakeResult, GetAccountInfoResult};
+use crate::harvest::SwapAction;
 
 // Callback
 #[ext_contract(this_contract)]
 pub trait ExtSelf {
-    fn on_get_draw_and_add_prize_distribution(&mut self, prize_awards: Balance, #[callback_result] call_result: Result<Draw, PromiseError>);
+    fn on_get_draw_and_add_prize_distribution(&mut self, redeems_started: u64, #[callback_result] call_result: Result<Draw, PromiseError>, #[callback_result] position_result: Result<Vec<Balance>, PromiseError>);
     fn on_get_draw_calculate_picks(&mut self, account_id: AccountId, #[callback_result] call_result: Result<Draw, PromiseError>) -> NumPicks;
     fn on_get_reward_from_defi(&self, #[callback_result] call_result: Result<Vec<TokenAmountsView>, PromiseError>)-> Balance;
-    fn on_after_rewards_claim_from_defi(&mut self, account_id: AccountId, amount: Balance, #[callback_result] result: Result<(), PromiseError>);
+    fn on_get_balance_from_defi(&self, #[callback_result] call_result: Result<Vec<TokenAmountsView>, PromiseError>)-> Balance;
+    fn on_near_unwrapped_stake(&mut self, metapool: AccountId, amount: Balance, #[callback_result] result: Result<(), PromiseError>) -> Promise;
+    fn on_near_staked(&mut self, metapool: AccountId, amount: Balance, #[callback_result] result: Result<(), PromiseError>) -> PromiseOrValue<U128>;
+    fn on_refunded_near_wrapped(&mut self, metapool: AccountId, amount: Balance, #[callback_result] result: Result<(), PromiseError>) -> U128;
+    fn on_get_account_info_unstake(&mut self, metapool: AccountId, amount: Balance, #[callback_result] call_result: Result<GetAccountInfoResult, PromiseError>) -> Promise;
+    fn on_liquid_unstaked(&mut self, amount: Balance, #[callback_result] call_result: Result<LiquidUnstakeResult, PromiseError>) -> Promise;
+    fn on_unstaked_near_wrapped(&mut self, amount: Balance, #[callback_result] result: Result<(), PromiseError>) -> U128;
+    fn on_near_wrapped(&mut self, amount: Balance, #[callback_result] result: Result<(), PromiseError>);
+    fn on_get_balance_from_metapool(&self, #[callback_result] call_result: Result<GetAccountInfoResult, PromiseError>) -> Balance;
+    fn on_get_reward_from_metapool(&self, #[callback_result] call_result: Result<GetAccountInfoResult, PromiseError>) -> Balance;
+    fn on_get_balance_redeem_for_migration(&mut self, #[callback_result] call_result: Result<Balance, PromiseError>) -> PromiseOrValue<()>;
+    fn on_redeemed_for_migration(&mut self, amount: Balance) -> PromiseOrValue<()>;
+    fn on_deposited_for_migration(&mut self, amount: Balance);
+    fn on_check_pool_accounting(&self) -> bool;
+    fn on_get_pool_position(&self) -> Vec<Balance>;
+    fn on_redeemed_for_rebalance(&mut self, from: YieldSource, to: YieldSource, amount: Balance) -> PromiseOrValue<()>;
+    fn on_deposited_for_rebalance(&mut self, from: Option<YieldSource>, to: YieldSource, amount: Balance);
+    fn on_deposited_to_yield_source(&mut self, source: YieldSource, amount: Balance);
+    fn on_redeemed_for_idle_buffer(&mut self, from: YieldSource, amount: Balance);
+    fn on_farm_rewards_claimed(&mut self, burrow: AccountId, reward_token_id: AccountId, #[callback_result] call_result: Result<Vec<TokenAmountsView>, PromiseError>) -> PromiseOrValue<U128>;
+    fn on_farm_rewards_withdrawn(&mut self, reward_token_id: AccountId, amount: Balance, #[callback_result] result: Result<(), PromiseError>) -> U128;
+    fn on_farm_rewards_sent_to_dex(&mut self, swap: SwapAction) -> PromiseOrValue<()>;
+    fn on_farm_rewards_swapped(&mut self, swap: SwapAction, #[callback_result] call_result: Result<U128, PromiseError>) -> Promise;
+    fn on_withdrawn_from_dex(&mut self, token_id: AccountId, amount: Balance, #[callback_result] deposit_result: Result<U128, PromiseError>);
+    fn on_prize_redeemed(&mut self, account_id: AccountId, claimed: Vec<(DrawId, NumPicks, Balance)>, redeems: Vec<(YieldSource, Balance)>);
+    fn on_after_claim(&mut self, account_id: AccountId, claimed: Vec<(DrawId, NumPicks, Balance)>, #[callback_result] result: Result<(), PromiseError>);
+    fn on_withdraw_from_defi(&mut self, account_id: AccountId, amount: Balance, source: YieldSource);
+    fn on_after_withdraw_transfer(&mut self, account_id: AccountId, amount: Balance, #[callback_result] result: Result<(), PromiseError>);
 }
 
 #[ext_contract(ext_draw)]
@@ -20,6 +49,7 @@ pub trait ExtDraw {
 pub trait ExtDeFi {
     fn show_reward(&self, account_id: AccountId) -> Vec<TokenAmountsView>;
     fn execute(&self, actions: Vec<Action>);
+    fn account_farm_claim_all(&mut self, account_id: Option<AccountId>);
 }
 
 #[ext_contract(ext_fungible_token)]
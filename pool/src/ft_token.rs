@@ -1,10 +1,14 @@
 use crate::*;
+use near_contract_standards::fungible_token::core::FungibleTokenCore;
+use near_contract_standards::fungible_token::resolver::FungibleTokenResolver;
 use near_sdk::ext_contract;
 
 #[near_bindgen]
 impl Contract{
     pub (crate) fn mint_tokens(&mut self, account_id: AccountId, balance: Balance){       
-        self.token.internal_register_account(&account_id);
+        if !self.token.accounts.contains_key(&account_id){
+            self.token.internal_register_account(&account_id);
+        }
         self.token.internal_deposit(&account_id, balance);
         
         near_contract_standards::fungible_token::events::FtMint {
@@ -17,14 +21,81 @@ impl Contract{
         let current_time = env::block_timestamp_ms();
         self.tickets.increase_balance(&account_id, balance, current_time);
         self.tickets.increase_total_supply(balance, current_time);
+        self.delegated_tickets.increase_balance(&account_id, balance, current_time);
     }
 
     pub (crate) fn burn_tokens(&mut self, account_id: AccountId, balance: Balance){
-        self.token.internal_transfer(&account_id, &AccountId::new_unchecked(ZERO_ADDRESS.to_string()), balance, Option::None);
+        self.token.internal_withdraw(&account_id, balance);
+
+        near_contract_standards::fungible_token::events::FtBurn {
+            owner_id: &account_id,
+            amount: &U128(balance),
+            memo: Some("Tokens supply is burned"),
+        }
+        .emit();
+
         let current_time = env::block_timestamp_ms();
 
         self.tickets.decrease_balance(&account_id, balance, current_time);
         self.tickets.decrease_total_supply(balance, current_time);
+        self.delegated_tickets.decrease_balance(&account_id, balance, current_time);
+    }
+
+    /// Moves the deposit history of `amount` tickets from `sender_id` to `receiver_id`,
+    /// so the odds of winning always follow the tickets
+    pub (crate) fn transfer_tickets_history(&mut self, sender_id: &AccountId, receiver_id: &AccountId, amount: Balance){
+        let current_time = env::block_timestamp_ms();
+
+        self.tickets.decrease_balance(sender_id, amount, current_time);
+        self.tickets.increase_balance(receiver_id, amount, current_time);
+        self.delegated_tickets.decrease_balance(sender_id, amount, current_time);
+        self.delegated_tickets.increase_balance(receiver_id, amount, current_time);
+    }
+
+    /// Sends the redeemed tokens to the account, at most `amount`. The tokens above it stay idle in the pool.
+    /// If Meta Pool unstaked less than `amount`, the account pays the rest of the fee
+    #[private]
+    pub fn on_withdraw_from_defi(&mut self, account_id: AccountId, amount: Balance, source: YieldSource){
+        self.yield_sources.end_redeem();
+        let redeemed = match utils::utils::redeemed_amount(amount){
+            Some(redeemed) => redeemed,
+            None => {
+                log!("Error when withdrawing from defi, minting back {} tickets to @{}", amount, account_id);
+                self.mint_tokens(account_id, amount);
+                self.accounting.on_deposit(amount);
+                self.yield_sources.credit(&source, amount);
+                return;
+            }
+        };
+
+        if redeemed == 0{
+            // the stNEAR is burned, the unstaked NEAR becomes idle once it is wrapped
+            log!("Nothing is redeemed, minting back {} tickets to @{}", amount, account_id);
+            self.mint_tokens(account_id, amount);
+            self.accounting.on_deposit(amount);
+            return;
+        }
+
+        let withdrawn = std::cmp::min(redeemed, amount);
+        self.yield_sources.debit_surplus(&source, amount, redeemed);
+        self.yield_sources.idle += redeemed - withdrawn;
+        if withdrawn < amount{
+            log!("{} of the {} withdrawn by @{} went to the unstake fee", amount - withdrawn, amount, account_id);
+        }
+
+        ext_fungible_token::ft_transfer(account_id.clone(), U128(withdrawn), None, self.deposited_token_id.clone(), 1, gas::GAS_FOR_FT_TRANSFER)
+        .then(this_contract::on_after_withdraw_transfer(account_id, withdrawn, env::current_account_id(), 0, gas::GAS_FOR_AFTER_FT_TRANSFER));
+    }
+
+    #[private]
+    pub fn on_after_withdraw_transfer(&mut self, account_id: AccountId, amount: Balance, #[callback_result] result: Result<(), PromiseError>){
+        if result.is_err(){
+            log!("Error when transferring withdrawn tokens, minting back {} tickets to @{}", amount, account_id);
+            self.mint_tokens(account_id, amount);
+            self.accounting.on_deposit(amount);
+            // the tokens are already out of the yield source
+            self.yield_sources.idle += amount;
+        }
     }
 
     pub (crate) fn on_tokens_burned(&mut self, account_id: AccountId, amount: Balance) {
@@ -37,3 +108,162 @@ impl Contract{
 
 }
 
+
+#[near_bindgen]
+impl FungibleTokenCore for Contract {
+    #[payable]
+    fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>) {
+        self.assert_tickets_migrated();
+        let sender_id = env::predecessor_account_id();
+        self.token.ft_transfer(receiver_id.clone(), amount, memo);
+        self.transfer_tickets_history(&sender_id, &receiver_id, amount.0);
+    }
+
+    #[payable]
+    fn ft_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        self.assert_tickets_migrated();
+        let sender_id = env::predecessor_account_id();
+        let result = self.token.ft_transfer_call(receiver_id.clone(), amount, memo, msg);
+        self.transfer_tickets_history(&sender_id, &receiver_id, amount.0);
+
+        return result;
+    }
+
+    fn ft_total_supply(&self) -> U128 {
+        self.token.ft_total_supply()
+    }
+
+    fn ft_balance_of(&self, account_id: AccountId) -> U128 {
+        self.token.ft_balance_of(account_id)
+    }
+}
+
+#[near_bindgen]
+impl FungibleTokenResolver for Contract {
+    #[private]
+    fn ft_resolve_transfer(
+        &mut self,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        amount: U128,
+    ) -> U128 {
+        let (used_amount, burned_amount) =
+            self.token.internal_ft_resolve_transfer(&sender_id, receiver_id.clone(), amount);
+
+        // Unused tickets are refunded back to the sender
+        if used_amount < amount.0 {
+            self.transfer_tickets_history(&receiver_id, &sender_id, amount.0 - used_amount);
+        }
+
+        // Sender's account was deleted, so the refunded tickets are burned.
+        // Nobody can withdraw their deposit anymore, it becomes yield
+        if burned_amount > 0 {
+            let current_time = env::block_timestamp_ms();
+            self.tickets.decrease_balance(&receiver_id, burned_amount, current_time);
+            self.tickets.decrease_total_supply(burned_amount, current_time);
+            self.delegated_tickets.decrease_balance(&receiver_id, burned_amount, current_time);
+            self.accounting.on_withdraw(burned_amount);
+            self.on_tokens_burned(sender_id, burned_amount);
+        }
+
+        return used_amount.into();
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use crate::*;
+    use crate::test_utils::{get_contract, mmmm, sec, burrow, set_promise_results, returned};
+    use near_contract_standards::fungible_token::core::FungibleTokenCore;
+    use near_contract_standards::fungible_token::resolver::FungibleTokenResolver;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn test_burn_decreases_tickets_and_twab(){
+        let mut contract = get_contract();
+
+        contract.mint_tokens(mmmm(), 100);
+        contract.mint_tokens(mmmm(), 50);
+        contract.burn_tokens(mmmm(), 30);
+
+        assert_eq!(contract.token.ft_balance_of(mmmm()).0, 120);
+        assert_eq!(contract.token.ft_total_supply().0, 120);
+        assert_eq!(contract.tickets.accounts.get(&mmmm()).unwrap().balance, 120);
+        assert_eq!(contract.tickets.total_supply.balance, 120);
+    }
+
+    #[test]
+    fn test_transfer_moves_twab_history(){
+        let mut contract = get_contract();
+        contract.mint_tokens(mmmm(), 100);
+        contract.token.internal_register_account(&sec());
+
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(mmmm())
+            .attached_deposit(1)
+            .block_timestamp(10_000_000)
+            .build());
+        contract.ft_transfer(sec(), U128(40), None);
+
+        assert_eq!(contract.token.ft_balance_of(mmmm()).0, 60);
+        assert_eq!(contract.token.ft_balance_of(sec()).0, 40);
+        assert_eq!(contract.tickets.accounts.get(&mmmm()).unwrap().balance, 60);
+        assert_eq!(contract.tickets.accounts.get(&sec()).unwrap().balance, 40);
+        assert_eq!(contract.tickets.total_supply.balance, 100);
+    }
+
+    #[test]
+    fn test_refund_to_deleted_account_burns_principal(){
+        let mut contract = get_contract();
+        contract.mint_tokens(mmmm(), 40);
+        contract.accounting.on_deposit(40);
+        contract.token.internal_register_account(&sec());
+        contract.token.internal_transfer(&mmmm(), &sec(), 40, None);
+        contract.transfer_tickets_history(&mmmm(), &sec(), 40);
+        contract.token.accounts.remove(&mmmm());
+
+        // the receiver didn't use the tickets, the sender doesn't exist anymore
+        set_promise_results(vec![returned(40)]);
+        // the burned tickets are reported as used
+        assert_eq!(contract.ft_resolve_transfer(mmmm(), sec(), U128(40)), U128(40));
+
+        assert_eq!(contract.token.ft_total_supply().0, 0);
+        assert_eq!(contract.tickets.accounts.get(&sec()).unwrap().balance, 0);
+        assert_eq!(contract.tickets.total_supply.balance, 0);
+        assert_eq!(contract.get_pool_accounting().total_principal, U128(0));
+    }
+
+    #[test]
+    fn test_withdraw_of_redeemed_amount(){
+        let mut contract = get_contract();
+        // the allocation was debited by the withdrawn amount
+        contract.yield_sources.sources[0].allocated = 100;
+        // the NEAR above the amount stays in the pool, its value is debited as well
+        set_promise_results(vec![returned(52)]);
+        contract.on_withdraw_from_defi(sec(), 50, YieldSource::Burrow { address: burrow() });
+        assert_eq!(contract.get_idle_balance(), U128(2));
+        assert_eq!(contract.yield_sources.sources[0].allocated, 98);
+
+        // the unstaked NEAR didn't cover the fee, only that much is sent
+        set_promise_results(vec![returned(48)]);
+        contract.on_withdraw_from_defi(sec(), 50, YieldSource::Burrow { address: burrow() });
+        assert_eq!(contract.get_idle_balance(), U128(2));
+        contract.on_after_withdraw_transfer(sec(), 48, Err(PromiseError::Failed));
+        assert_eq!(contract.token.ft_balance_of(sec()), U128(48));
+        assert_eq!(contract.get_pool_accounting().total_principal, U128(48));
+
+        // the unstaked NEAR wasn't wrapped, the tickets are minted back
+        set_promise_results(vec![returned(0)]);
+        contract.on_withdraw_from_defi(sec(), 50, YieldSource::Burrow { address: burrow() });
+        assert_eq!(contract.get_idle_balance(), U128(50));
+        assert_eq!(contract.token.ft_balance_of(sec()), U128(98));
+        assert_eq!(contract.get_pool_accounting().total_principal, U128(98));
+    }
+}
@@ -0,0 +1,396 @@
+use near_sdk::collections::LookupMap;
+use utils::storage_keys::StorageKeys;
+use crate::events::events;
+use crate::*;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SwapAction{
+    pub pool_id: u64,
+    pub token_in: AccountId,
+    pub amount_in: Option<U128>,
+    pub token_out: AccountId,
+    pub min_amount_out: U128,
+}
+
+#[ext_contract(ext_dex)]
+pub trait ExtDex {
+    fn swap(&mut self, actions: Vec<SwapAction>, referral_id: Option<AccountId>) -> U128;
+    fn withdraw(&mut self, token_id: AccountId, amount: U128, unregister: Option<bool>) -> Promise;
+    fn get_deposit(&self, account_id: AccountId, token_id: AccountId) -> U128;
+}
+
+/// The farm rewards of the yield sources are paid in other tokens,
+/// they are swapped to the deposited token and added to the prize
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct FarmRewards{
+    /// The Ref Finance style exchange the rewards are swapped on
+    pub dex: Option<AccountId>,
+    /// The reward tokens held by the pool that are not swapped yet
+    pub harvested: LookupMap<AccountId, Balance>,
+    /// The tokens of the pool left in the DEX by a failed withdraw
+    pub in_dex: LookupMap<AccountId, Balance>,
+}
+
+impl Default for FarmRewards{
+    fn default() -> Self {
+        FarmRewards {
+            dex: None,
+            harvested: LookupMap::new(StorageKeys::HarvestedFarmRewards),
+            in_dex: LookupMap::new(StorageKeys::FarmRewardsInDex),
+        }
+    }
+}
+
+impl FarmRewards{
+    pub fn add_harvested(&mut self, token_id: &AccountId, amount: Balance){
+        let harvested = self.harvested.get(token_id).unwrap_or(0);
+        self.harvested.insert(token_id, &(harvested + amount));
+    }
+
+    pub fn set_in_dex(&mut self, token_id: &AccountId, amount: Balance){
+        if amount == 0{
+            self.in_dex.remove(token_id);
+        } else {
+            self.in_dex.insert(token_id, &amount);
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract{
+    pub fn get_dex(&self) -> Option<AccountId>{
+        return self.farm_rewards.dex.clone();
+    }
+
+    #[payable]
+    pub fn set_dex(&mut self, dex: AccountId){
+        assert_one_yocto();
+        self.assert_role(Role::Admin);
+        self.farm_rewards.dex = Some(dex);
+    }
+
+    pub fn get_harvested_farm_rewards(&self, token_id: AccountId) -> U128{
+        return U128(self.farm_rewards.harvested.get(&token_id).unwrap_or(0));
+    }
+
+    pub fn get_farm_rewards_in_dex(&self, token_id: AccountId) -> U128{
+        return U128(self.farm_rewards.in_dex.get(&token_id).unwrap_or(0));
+    }
+
+    /// Claims the farm rewards of the pool in Burrow and takes the `reward_token_id` out of it
+    pub fn harvest_farm_rewards(&mut self, source: YieldSource, reward_token_id: AccountId) -> Promise{
+        self.assert_role(Role::Keeper);
+        self.assert_not_paused(Operation::YieldTransfer);
+        assert!(reward_token_id != self.deposited_token_id, "The deposited token is not a farm reward");
+        assert!(self.yield_sources.find(&source).is_some(), "There is no such yield source");
+
+        let address = match source{
+            YieldSource::Burrow { address } => address,
+            _ => panic!("Only Burrow pays farm rewards"),
+        };
+
+        return ext_defi::account_farm_claim_all(None, address.clone(), 0, gas::GAS_FOR_FARM_CLAIM)
+            .then(ext_defi::show_reward(env::current_account_id(), address.clone(), 0, gas::GAS_FOR_FT_TRANSFER))
+            .then(this_contract::on_farm_rewards_claimed(address, reward_token_id, env::current_account_id(), 0, gas::GAS_FOR_AFTER_FARM_CLAIM));
+    }
+
+    #[private]
+    pub fn on_farm_rewards_claimed(&mut self, burrow: AccountId, reward_token_id: AccountId, #[callback_result] call_result: Result<Vec<TokenAmountsView>, PromiseError>) -> PromiseOrValue<U128>{
+        if call_result.is_err(){
+            panic!("Error when claiming the farm rewards");
+        }
+
+        let amount = call_result
+            .unwrap()
+            .iter()
+            .find(|el| el.token == reward_token_id)
+            .map_or(0, |el| el.shares.0 + el.rewards.0);
+        if amount == 0{
+            log!("There are no {} farm rewards", reward_token_id);
+            return PromiseOrValue::Value(U128(0));
+        }
+
+        let action = Action::Withdraw(AssetAmount { token_id: reward_token_id.clone(), amount: Some(U128(amount)), max_amount: None });
+        let promise = ext_defi::execute(vec![action], burrow, 1, gas::GAS_FOR_WITHDRAW_FROM_DEFI)
+            .then(this_contract::on_farm_rewards_withdrawn(reward_token_id, amount, env::current_account_id(), 0, gas::GAS_FOR_AFTER_FARM_WITHDRAW));
+
+        return PromiseOrValue::Promise(promise);
+    }
+
+    #[private]
+    pub fn on_farm_rewards_withdrawn(&mut self, reward_token_id: AccountId, amount: Balance, #[callback_result] result: Result<(), PromiseError>) -> U128{
+        if result.is_err(){
+            panic!("Error when withdrawing {} {} from Burrow", amount, reward_token_id);
+        }
+
+        self.farm_rewards.add_harvested(&reward_token_id, amount);
+        events::farm_rewards_harvested(&reward_token_id, amount);
+
+        return U128(amount);
+    }
+
+    /// Swaps the harvested `reward_token_id` to the deposited token in the `pool_id` of the DEX.
+    /// The swap fails if it returns less than `min_amount_out`
+    pub fn swap_farm_rewards(&mut self, reward_token_id: AccountId, pool_id: u64, min_amount_out: U128) -> Promise{
+        self.assert_role(Role::Keeper);
+        self.assert_not_paused(Operation::YieldTransfer);
+        let amount = self.farm_rewards.harvested.get(&reward_token_id).unwrap_or(0);
+        assert!(amount > 0, "There are no harvested {} farm rewards", reward_token_id);
+        self.farm_rewards.harvested.remove(&reward_token_id);
+
+        let swap = SwapAction {
+            pool_id: pool_id,
+            token_in: reward_token_id.clone(),
+            amount_in: Some(U128(amount)),
+            token_out: self.deposited_token_id.clone(),
+            min_amount_out: min_amount_out,
+        };
+
+        return self
+            .send_to_dex(&reward_token_id, amount)
+            .then(this_contract::on_farm_rewards_sent_to_dex(swap, env::current_account_id(), 0, gas::GAS_FOR_AFTER_SEND_TO_DEX));
+    }
+
+    #[private]
+    pub fn on_farm_rewards_sent_to_dex(&mut self, swap: SwapAction) -> PromiseOrValue<()>{
+        let amount = swap.amount_in.unwrap().0;
+        let deposited = utils::utils::deposited_amount(amount);
+        if deposited < amount{
+            log!("{} {} is not deposited in the DEX", amount - deposited, swap.token_in);
+            self.farm_rewards.add_harvested(&swap.token_in, amount - deposited);
+        }
+
+        if deposited == 0{
+            return PromiseOrValue::Value(());
+        }
+
+        let dex = self.farm_rewards.dex.clone().expect("The DEX is not set");
+        let swap = SwapAction { amount_in: Some(U128(deposited)), ..swap };
+        let promise = ext_dex::swap(vec![swap.clone()], None, dex, 0, gas::GAS_FOR_DEX_SWAP)
+            .then(this_contract::on_farm_rewards_swapped(swap, env::current_account_id(), 0, gas::GAS_FOR_AFTER_DEX_SWAP));
+
+        return PromiseOrValue::Promise(promise);
+    }
+
+    /// A failed swap, like one below the minimum output, leaves the reward tokens in the DEX.
+    /// They are withdrawn back
+    #[private]
+    pub fn on_farm_rewards_swapped(&mut self, swap: SwapAction, #[callback_result] call_result: Result<U128, PromiseError>) -> Promise{
+        let amount_in = swap.amount_in.unwrap().0;
+
+        return match call_result{
+            Ok(amount_out) => {
+                events::farm_rewards_swapped(&swap.token_in, amount_in, amount_out.0);
+                self.withdraw_from_dex(&swap.token_out, amount_out.0)
+            },
+            Err(_) => {
+                log!("Error when swapping {} {}", amount_in, swap.token_in);
+                self.withdraw_from_dex(&swap.token_in, amount_in)
+            },
+        };
+    }
+
+    /// Withdraws the `token_id` left in the DEX by a failed withdraw
+    pub fn withdraw_farm_rewards_from_dex(&mut self, token_id: AccountId) -> Promise{
+        self.assert_role(Role::Keeper);
+        self.assert_not_paused(Operation::YieldTransfer);
+        let amount = self.farm_rewards.in_dex.get(&token_id).unwrap_or(0);
+        assert!(amount > 0, "There are no {} tokens of the pool in the DEX", token_id);
+
+        return self.withdraw_from_dex(&token_id, 0);
+    }
+
+    /// The DEX withdraw succeeds even if its transfer fails, the tokens are then put back in the
+    /// deposit of the pool. What is still deposited was not received and is kept for another withdraw
+    #[private]
+    pub fn on_withdrawn_from_dex(&mut self, token_id: AccountId, amount: Balance, #[callback_result] deposit_result: Result<U128, PromiseError>){
+        let left_in_dex = match deposit_result{
+            Ok(deposit) => std::cmp::min(deposit.0, amount),
+            Err(_) => {
+                log!("Error when getting the {} deposit in the DEX, {} is kept for another withdraw", token_id, amount);
+                self.farm_rewards.set_in_dex(&token_id, amount);
+                return;
+            },
+        };
+        if left_in_dex > 0{
+            log!("{} {} is left in the DEX", left_in_dex, token_id);
+        }
+        self.farm_rewards.set_in_dex(&token_id, left_in_dex);
+
+        let withdrawn = amount - left_in_dex;
+        if withdrawn == 0{
+            return;
+        }
+
+        if token_id == self.deposited_token_id{
+            // the swapped tokens stay idle in the pool and are added to the next prize distribution
+            self.yield_sources.idle += withdrawn;
+            self.accounting.rollover += withdrawn;
+        } else {
+            self.farm_rewards.add_harvested(&token_id, withdrawn);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use crate::*;
+    use crate::test_utils::{get_contract, mmmm, sec, burrow, usdc, set_context, set_promise_results, returned, position};
+
+    use super::SwapAction;
+
+    fn reward_token() -> AccountId{
+        AccountId::new_unchecked("reward".to_string())
+    }
+
+    fn swap_action(amount_in: Balance, min_amount_out: Balance) -> SwapAction{
+        return SwapAction {
+            pool_id: 0,
+            token_in: reward_token(),
+            amount_in: Some(U128(amount_in)),
+            token_out: usdc(),
+            min_amount_out: U128(min_amount_out),
+        };
+    }
+
+    #[test]
+    fn test_harvest_and_swap_farm_rewards(){
+        let mut contract = get_contract();
+        set_context(mmmm());
+        contract.set_dex(sec());
+
+        set_context(mmmm());
+        contract.on_farm_rewards_claimed(burrow(), reward_token(), Ok(vec![position(usdc(), 100, 0), position(reward_token(), 0, 30)]));
+        contract.on_farm_rewards_withdrawn(reward_token(), 30, Ok(()));
+        assert_eq!(contract.get_harvested_farm_rewards(reward_token()), U128(30));
+
+        set_context(mmmm());
+        contract.swap_farm_rewards(reward_token(), 0, U128(50));
+        assert_eq!(contract.get_harvested_farm_rewards(reward_token()), U128(0));
+
+        // the DEX takes only 20 of the rewards
+        set_promise_results(vec![returned(20)]);
+        contract.on_farm_rewards_sent_to_dex(swap_action(30, 50));
+        assert_eq!(contract.get_harvested_farm_rewards(reward_token()), U128(10));
+
+        set_context(mmmm());
+        contract.on_farm_rewards_swapped(swap_action(20, 50), Ok(U128(60)));
+        contract.on_withdrawn_from_dex(usdc(), 60, Ok(U128(0)));
+        assert_eq!(contract.get_idle_balance(), U128(60));
+        assert_eq!(contract.get_prize_rollover(), U128(60));
+    }
+
+    #[test]
+    fn test_failed_dex_transfer_is_kept_in_dex(){
+        let mut contract = get_contract();
+        set_context(mmmm());
+        contract.set_dex(sec());
+
+        // the transfer of the swapped tokens fails, the DEX puts them back in the deposit
+        set_context(mmmm());
+        contract.on_farm_rewards_swapped(swap_action(20, 50), Ok(U128(60)));
+        contract.on_withdrawn_from_dex(usdc(), 60, Ok(U128(60)));
+        assert_eq!(contract.get_idle_balance(), U128(0));
+        assert_eq!(contract.get_prize_rollover(), U128(0));
+        assert_eq!(contract.get_farm_rewards_in_dex(usdc()), U128(60));
+
+        // the keeper withdraws them again together with the next swap
+        set_context(mmmm());
+        contract.on_farm_rewards_swapped(swap_action(10, 20), Ok(U128(30)));
+        assert_eq!(contract.get_farm_rewards_in_dex(usdc()), U128(0));
+        contract.on_withdrawn_from_dex(usdc(), 90, Ok(U128(0)));
+        assert_eq!(contract.get_idle_balance(), U128(90));
+        assert_eq!(contract.get_prize_rollover(), U128(90));
+
+        set_context(mmmm());
+        contract.on_farm_rewards_swapped(swap_action(20, 50), Err(PromiseError::Failed));
+        contract.on_withdrawn_from_dex(reward_token(), 20, Ok(U128(20)));
+        assert_eq!(contract.get_farm_rewards_in_dex(reward_token()), U128(20));
+
+        set_context(mmmm());
+        contract.withdraw_farm_rewards_from_dex(reward_token());
+        contract.on_withdrawn_from_dex(reward_token(), 20, Ok(U128(0)));
+        assert_eq!(contract.get_farm_rewards_in_dex(reward_token()), U128(0));
+        assert_eq!(contract.get_harvested_farm_rewards(reward_token()), U128(20));
+    }
+
+    #[test]
+    fn test_failed_swap_returns_farm_rewards(){
+        let mut contract = get_contract();
+        set_context(mmmm());
+        contract.set_dex(sec());
+
+        contract.on_farm_rewards_swapped(swap_action(20, 50), Err(PromiseError::Failed));
+        contract.on_withdrawn_from_dex(reward_token(), 20, Ok(U128(0)));
+        assert_eq!(contract.get_harvested_farm_rewards(reward_token()), U128(20));
+        assert_eq!(contract.get_prize_rollover(), U128(0));
+    }
+
+    #[test]
+    fn test_dex_deposit_unknown_is_kept_in_dex(){
+        let mut contract = get_contract();
+        set_context(mmmm());
+        contract.set_dex(sec());
+
+        // the deposit is unknown, the next withdraw books the tokens whether they were received or not
+        set_context(mmmm());
+        contract.on_farm_rewards_swapped(swap_action(20, 50), Ok(U128(60)));
+        contract.on_withdrawn_from_dex(usdc(), 60, Err(PromiseError::Failed));
+        assert_eq!(contract.get_idle_balance(), U128(0));
+        assert_eq!(contract.get_farm_rewards_in_dex(usdc()), U128(60));
+
+        set_context(mmmm());
+        contract.withdraw_farm_rewards_from_dex(usdc());
+        contract.on_withdrawn_from_dex(usdc(), 60, Ok(U128(0)));
+        assert_eq!(contract.get_farm_rewards_in_dex(usdc()), U128(0));
+        assert_eq!(contract.get_idle_balance(), U128(60));
+    }
+
+    #[test]
+    #[should_panic(expected = "Only Burrow pays farm rewards")]
+    fn test_harvest_from_metapool(){
+        let mut contract = get_contract();
+        let metapool = YieldSource::Metapool { address: AccountId::new_unchecked("metapool".to_string()) };
+        set_context(mmmm());
+        contract.add_yield_source(metapool.clone(), 100);
+        contract.harvest_farm_rewards(metapool, reward_token());
+    }
+
+    #[test]
+    #[should_panic(expected = "There are no reward tokens of the pool in the DEX")]
+    fn test_withdraw_from_dex_without_tokens_left(){
+        let mut contract = get_contract();
+        set_context(mmmm());
+        contract.set_dex(sec());
+        contract.withdraw_farm_rewards_from_dex(reward_token());
+    }
+
+    #[test]
+    #[should_panic(expected = "YieldTransfer is paused")]
+    fn test_swap_when_paused(){
+        let mut contract = get_contract();
+        contract.farm_rewards.add_harvested(&reward_token(), 30);
+        set_context(mmmm());
+        contract.pause(vec![Operation::YieldTransfer]);
+        contract.swap_farm_rewards(reward_token(), 0, U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "YieldTransfer is paused")]
+    fn test_withdraw_from_dex_when_paused(){
+        let mut contract = get_contract();
+        contract.farm_rewards.set_in_dex(&usdc(), 60);
+        set_context(mmmm());
+        contract.pause(vec![Operation::YieldTransfer]);
+        contract.withdraw_farm_rewards_from_dex(usdc());
+    }
+
+    #[test]
+    #[should_panic(expected = "There are no harvested reward farm rewards")]
+    fn test_swap_without_farm_rewards(){
+        let mut contract = get_contract();
+        set_context(mmmm());
+        contract.swap_farm_rewards(reward_token(), 0, U128(0));
+    }
+}
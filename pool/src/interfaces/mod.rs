@@ -1,13 +1,14 @@
 pub mod pool {
 
-    use near_sdk::{AccountId, Balance};
+    use near_sdk::{AccountId, Balance, Promise};
 
-    use super::defi::IYieldSource;
+    use super::defi::{IYieldSource, YieldSource};
     pub trait IPool{
         fn assert_correct_token_is_send_to_contract(&self, token: &AccountId);
         fn get_lottery_asset(&self) -> AccountId;
-        fn send_to_dex(&self);
-        fn get_yield_source(&self) -> Box<dyn IYieldSource>;
+        fn send_to_dex(&self, token_id: &AccountId, amount: Balance) -> Promise;
+        fn withdraw_from_dex(&mut self, token_id: &AccountId, amount: Balance) -> Promise;
+        fn get_yield_source(&self, source: &YieldSource) -> Box<dyn IYieldSource>;
     }
 
     pub trait ITwab{
@@ -28,16 +29,21 @@ pub mod pool {
             start_time: u64, 
             end_time: u64
         ) -> Balance;
+
+        fn balance_at(&self, account: &AccountId, target_time: u64) -> Balance;
+        fn total_supply_at(&self, target_time: u64) -> Balance;
     }
 
 }
 
 pub mod defi {
     use near_sdk::{Balance, borsh::{self, BorshDeserialize, BorshSerialize}, AccountId, PromiseOrValue, Promise};
+    use near_sdk::serde::{Serialize, Deserialize};
 
     use crate::Contract;
 
-    #[derive(BorshDeserialize, BorshSerialize)]
+    #[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+    #[serde(crate = "near_sdk::serde")]
     pub enum YieldSource{
         Burrow { address: AccountId },
         Metapool { address: AccountId }
@@ -45,13 +51,17 @@ pub mod defi {
 
     pub trait IYieldSource{
         fn get_reward(&self, account_id: &AccountId) -> Promise;
-        fn transfer(&self, token_id: &AccountId, amount: Balance);
-        fn claim(&self, account_id: &AccountId, token_id:&AccountId, amount: Balance);
+        fn get_balance(&self, account_id: &AccountId) -> Promise;
+        fn transfer(&self, token_id: &AccountId, amount: Balance) -> Promise;
+        /// Takes `amount` of the token out of the yield source to the pool.
+        /// If the pool can get a different amount, the promise returns it as `U128`
+        fn redeem(&self, token_id: &AccountId, amount: Balance) -> Promise;
+        fn withdraw(&self, account_id: &AccountId, token_id: &AccountId, amount: Balance) -> Promise;
     }
 }
 
 pub mod prize_distribution{
-    const MAX_TIERS:usize = 16;
+    pub const MAX_TIERS:usize = 16;
     use common::types::{NumPicks, DrawId, WinningNumber};
     use near_sdk::Balance;
     use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
@@ -66,17 +76,50 @@ pub mod prize_distribution{
         pub cardinality: u8,
         pub bit_range_size: u8,
         pub tiers: [u32; MAX_TIERS],
+        /// The sum of the tiers at the time the distribution was created
+        pub tiers_nominal: u128,
         pub prize: u128,
+        /// The part of the prize that is already paid to the winners
+        pub claimed: u128,
+        /// Set when the unclaimed prize is swept after the end of the claiming
+        pub swept: bool,
         pub max_picks: u128,
         pub start_time: u64,
         pub end_time: u64,
+        /// The picks and the prize are calculated from the deposits after this time
+        pub draw_started_at: u64,
         #[serde(skip_serializing)]
         pub winning_number: WinningNumber,
     }
+
+    /// Parameters used for every prize distribution created after they are set
+    #[derive(BorshDeserialize, BorshSerialize, Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+    #[serde(crate = "near_sdk::serde")]
+    pub struct PrizeConfig{
+        pub tiers: [u32; MAX_TIERS],
+        pub tiers_nominal: u128,
+        pub bit_range_size: u8,
+        pub max_cardinality: u8,
+        pub min_pick_cost: Balance,
+        /// Time in ms after the draw is completed when the claiming starts.
+        /// The claiming lasts for the same period
+        pub distribution_time_offset: u64,
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+    #[serde(crate = "near_sdk::serde")]
+    pub struct PickPrizeView{
+        pub draw_id: U128,
+        pub pick: U128,
+        pub tier: u8,
+        pub amount: U128,
+    }
+
     pub trait PrizeDistributionActor{
         fn get_prize_distribution(&self, draw_id: u128) -> PrizeDistribution;
-        fn add_prize_distribution(&mut self, draw_id: u128, prize_awards: Balance);
+        fn add_prize_distribution(&mut self, draw_id: u128);
         fn claim(&mut self, draw_id: U128, pick: U128) -> u128;
+        fn claim_many(&mut self, claims: Vec<(U128, Vec<U128>)>) -> Vec<PickPrizeView>;
     }
 }
 
@@ -1,4 +1,5 @@
 use burrow::BurrowYieldSource;
+use metapool::MetapoolYieldSource;
 use near_contract_standards::fungible_token::metadata::{
     FungibleTokenMetadata, FungibleTokenMetadataProvider, FT_METADATA_SPEC,
 };
@@ -8,17 +9,25 @@ use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::collections::LazyOption;
 use near_sdk::json_types::U128;
 use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::{env, log, near_bindgen, AccountId, Balance, PanicOnDefault, PromiseOrValue, assert_one_yocto, ext_contract, PromiseError};
+use near_sdk::{env, log, near_bindgen, AccountId, Balance, PanicOnDefault, Promise, PromiseOrValue, assert_one_yocto, ext_contract, PromiseError};
 use interfaces::pool::{IPool, ITwab};
 use interfaces::defi::IYieldSource;
 use picks::AccountsPicks;
-use twab::AccountsDepositHistory;
+use claims::AccountsClaims;
+use accounting::PoolAccounting;
+use twab::{AccountsDepositHistory, AccountsDelegatedHistory};
 use prize::PrizeBuffer;
+use interfaces::prize_distribution::PrizeConfig;
 use common::types::{DrawId, NumPicks, WinningNumber};
 use interfaces::defi::YieldSource;
 use utils::gas;
-
-const ZERO_ADDRESS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+use access_control::Role;
+use common::access_control::AccessControl;
+use pause::Operation;
+use migration::YieldSourceMigration;
+use yield_sources::YieldSources;
+use harvest::{FarmRewards, ext_dex};
+use upgrade::TicketsMigration;
 
 pub mod external;
 pub use crate::external::*;
@@ -28,9 +37,20 @@ mod interfaces;
 mod twab;
 mod prize;
 mod picks;
+mod claims;
+mod accounting;
 mod utils;
+#[cfg(test)]
 mod test_utils;
 mod burrow;
+mod metapool;
+mod migration;
+mod yield_sources;
+mod harvest;
+mod events;
+mod access_control;
+mod pause;
+mod upgrade;
 
 const PROTOCOL_FT_SYMBOL: &str = "PTTICK";
 const PROTOCOL_FT_NAME: &str = "Pool Together Ticket";
@@ -62,10 +82,19 @@ pub struct Contract {
     // the token that is going to be used
     deposited_token_id: AccountId,
     tickets: AccountsDepositHistory,
+    delegated_tickets: AccountsDelegatedHistory,
     prizes: PrizeBuffer,
+    prize_config: PrizeConfig,
+    accounting: PoolAccounting,
     draw_contract: AccountId,
     acc_picks: AccountsPicks,
-    yield_source: YieldSource,
+    claims: AccountsClaims,
+    yield_sources: YieldSources,
+    migration: Option<YieldSourceMigration>,
+    farm_rewards: FarmRewards,
+    access_control: AccessControl<Role>,
+    paused_operations: Vec<Operation>,
+    tickets_migration: Option<TicketsMigration>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -96,17 +125,31 @@ impl IPool for Contract{
         self.deposited_token_id.clone()
     }
 
-    fn send_to_dex(&self) {
-        todo!()
+    /// Deposits `amount` of the token in the DEX, so it can be swapped
+    fn send_to_dex(&self, token_id: &AccountId, amount: Balance) -> Promise {
+        let dex = self.farm_rewards.dex.clone().expect("The DEX is not set");
+
+        return ext_fungible_token::ft_transfer_call(dex, U128(amount), None, "".to_string(), token_id.clone(), 1, gas::GAS_FOR_SEND_TO_DEX);
     }
 
-    fn get_yield_source(&self) -> Box<dyn IYieldSource> {
-        return match &self.yield_source{
+    /// Withdraws `amount` of the token from the DEX together with what an earlier withdraw left there.
+    /// The deposit of the pool is read after the withdraw to find out what was transferred
+    fn withdraw_from_dex(&mut self, token_id: &AccountId, amount: Balance) -> Promise{
+        let dex = self.farm_rewards.dex.clone().expect("The DEX is not set");
+        let amount = amount + self.farm_rewards.in_dex.get(token_id).unwrap_or(0);
+        self.farm_rewards.in_dex.remove(token_id);
+
+        return ext_dex::withdraw(token_id.clone(), U128(amount), None, dex.clone(), 1, gas::GAS_FOR_DEX_WITHDRAW)
+            .then(ext_dex::get_deposit(env::current_account_id(), token_id.clone(), dex, 0, gas::GAS_FOR_DEX_GET_DEPOSIT))
+            .then(this_contract::on_withdrawn_from_dex(token_id.clone(), amount, env::current_account_id(), 0, gas::GAS_FOR_AFTER_DEX_WITHDRAW));
+    }
+
+    fn get_yield_source(&self, source: &YieldSource) -> Box<dyn IYieldSource> {
+        return match source{
             YieldSource::Burrow { address } =>
                 Box::new(BurrowYieldSource{address: address.clone()}),
             YieldSource::Metapool { address } =>
-                panic!("Not implemented for Metapool"),
-            _ => panic!("No default option"),
+                Box::new(MetapoolYieldSource{address: address.clone()}),
         };
     }
 }
@@ -117,6 +160,13 @@ impl Contract {
     /// default metadata (for example purposes only).
     #[init]
     pub fn new_default_meta(owner_id: AccountId, token_for_deposit: AccountId, draw_contract: AccountId, burrow_address: AccountId) -> Self {
+        Self::new_with_yield_source(owner_id, token_for_deposit, draw_contract, YieldSource::Burrow { address: burrow_address })
+    }
+
+    /// Initializes the contract with default metadata and the given yield source,
+    /// more can be added with `add_yield_source`. For Metapool the deposited token should be wNEAR
+    #[init]
+    pub fn new_with_yield_source(owner_id: AccountId, token_for_deposit: AccountId, draw_contract: AccountId, yield_source: YieldSource) -> Self {
         Self::new(
             owner_id,
             token_for_deposit,
@@ -130,7 +180,7 @@ impl Contract {
                 decimals: 3,
             },
             draw_contract,
-            burrow_address,
+            yield_source,
         )
     }
 
@@ -145,7 +195,7 @@ impl Contract {
         deposited_token_id: AccountId,
         metadata: FungibleTokenMetadata,
         draw_contract: AccountId,
-        burrow_address: AccountId,
+        yield_source: YieldSource,
     ) -> Self {
         assert!(!env::state_exists(), "Already initialized");
         metadata.assert_valid();
@@ -154,10 +204,19 @@ impl Contract {
             metadata: LazyOption::new(b"m".to_vec(), Some(&metadata)),
             deposited_token_id: deposited_token_id,
             tickets: AccountsDepositHistory::default(),
+            delegated_tickets: AccountsDelegatedHistory::default(),
             prizes: PrizeBuffer::new(),
+            prize_config: PrizeConfig::default(),
+            accounting: PoolAccounting::default(),
             draw_contract: draw_contract,
             acc_picks: AccountsPicks::default(),
-            yield_source: YieldSource::Burrow { address: burrow_address },
+            claims: AccountsClaims::default(),
+            yield_sources: YieldSources::new(yield_source),
+            migration: None,
+            farm_rewards: FarmRewards::default(),
+            access_control: AccessControl::new(owner_id.clone(), utils::storage_keys::StorageKeys::Roles),
+            paused_operations: Vec::new(),
+            tickets_migration: None,
         };
 
         this.token.internal_register_account(&owner_id);
@@ -175,9 +234,35 @@ impl Contract {
     pub fn get_asset(&self) -> AccountId {
         self.deposited_token_id.clone()
     }
+
+    /// Burns `amount` tickets of the caller and sends back the same amount of the deposited token,
+    /// taken out of the idle tokens if they cover it, otherwise out of the most over-allocated yield source
+    /// that covers it. At most `get_max_withdrawal` can be withdrawn at once.
+    /// If the withdrawal from the yield source or the transfer fails, the tickets are minted back
+    #[payable]
+    pub fn withdraw(&mut self, amount: U128) -> Promise {
+        assert_one_yocto();
+        self.assert_not_paused(Operation::YieldTransfer);
+        assert!(amount.0 > 0, "The amount should be a positive number");
+
+        let caller = env::predecessor_account_id();
+        self.burn_tokens(caller.clone(), amount.0);
+        self.accounting.on_withdraw(amount.0);
+
+        if self.yield_sources.take_idle(amount.0){
+            return ext_fungible_token::ft_transfer(caller.clone(), amount, None, self.deposited_token_id.clone(), 1, gas::GAS_FOR_FT_TRANSFER)
+                .then(this_contract::on_after_withdraw_transfer(caller, amount.0, env::current_account_id(), 0, gas::GAS_FOR_AFTER_FT_TRANSFER));
+        }
+
+        let source = self.yield_sources.allocate_withdrawal(amount.0);
+        self.yield_sources.start_redeem();
+
+        return self
+            .get_yield_source(&source)
+            .withdraw(&caller, &self.deposited_token_id, amount.0);
+    }
 }
 
-near_contract_standards::impl_fungible_token_core!(Contract, token, on_tokens_burned);
 near_contract_standards::impl_fungible_token_storage!(Contract, token, on_account_closed);
 
 #[near_bindgen]
@@ -196,11 +281,16 @@ impl FungibleTokenReceiver for Contract{
         msg: String,
     ) -> PromiseOrValue<U128> {
         self.assert_correct_token_is_send_to_contract(&env::predecessor_account_id());
-        self
-            .get_yield_source()
-            .transfer(&env::predecessor_account_id(), amount.0);
+
+        // Returning the whole amount refunds it to the sender
+        if self.is_operation_paused(Operation::Deposit) || self.is_operation_paused(Operation::YieldTransfer){
+            log!("Deposits are paused, refunding {} to @{}", amount.0, sender_id);
+            return PromiseOrValue::Value(amount);
+        }
 
         self.mint_tokens(sender_id, amount.0);
+        self.accounting.on_deposit(amount.0);
+        self.deposit_to_yield_sources(&env::predecessor_account_id(), amount.0);
 
         return PromiseOrValue::Value(U128(0));
     }
@@ -209,7 +299,7 @@ impl FungibleTokenReceiver for Contract{
 #[cfg(test)]
 mod tests {
     use near_sdk::{collections::Vector, AccountId, Balance, env};
-    use crate::{twab::AccountsDepositHistory, twab::AccountBalance, interfaces::{pool::ITwab, prize_distribution::PrizeDistribution}};
+    use crate::{twab::AccountsDepositHistory, twab::AccountBalance, twab::Twab, twab::TwabBuffer, interfaces::{pool::ITwab, prize_distribution::PrizeDistribution}};
     use common::{generic_ring_buffer::GenericRingBuffer, types::U256};
 
     fn mint(tickets: &mut AccountsDepositHistory, acc_id: &AccountId, amount: Balance, time: u64){
@@ -289,25 +379,25 @@ mod tests {
     fn verify_setup(){
         let setup = setup();
         assert_eq!(setup.total_supply.balance, 100);
-        assert_eq!(setup.total_supply.twabs.get(0).unwrap_or_default().amount, 0);
-        assert_eq!(setup.total_supply.twabs.get(1).unwrap_or_default().amount, 500);
-        assert_eq!(setup.total_supply.twabs.get(2).unwrap_or_default().amount, 1150);
-        assert_eq!(setup.total_supply.twabs.get(3).unwrap_or_default().amount, 2950);
-        assert_eq!(setup.total_supply.twabs.get(4).unwrap_or_default().amount, 3430);
+        assert_eq!(setup.total_supply.twabs.get(0).unwrap_or_default().amount, U256::from(0));
+        assert_eq!(setup.total_supply.twabs.get(1).unwrap_or_default().amount, U256::from(500));
+        assert_eq!(setup.total_supply.twabs.get(2).unwrap_or_default().amount, U256::from(1150));
+        assert_eq!(setup.total_supply.twabs.get(3).unwrap_or_default().amount, U256::from(2950));
+        assert_eq!(setup.total_supply.twabs.get(4).unwrap_or_default().amount, U256::from(3430));
 
-        let acc_sec : AccountBalance = setup.accounts.get(&sec()).unwrap_or_default();
-        let acc_mmm : AccountBalance = setup.accounts.get(&mmmm()).unwrap_or_default();
+        let acc_sec : AccountBalance = setup.accounts.get(&sec()).unwrap();
+        let acc_mmm : AccountBalance = setup.accounts.get(&mmmm()).unwrap();
 
         assert_eq!(acc_mmm.balance, 40);
-        assert_eq!(acc_mmm.twabs.get(0).unwrap_or_default().amount, 0);
-        assert_eq!(acc_mmm.twabs.get(1).unwrap_or_default().amount, 1000);
-        assert_eq!(acc_mmm.twabs.get(2).unwrap_or_default().amount, 2500);
-        assert_eq!(acc_mmm.twabs.get(3).unwrap_or_default().amount, 3000);
+        assert_eq!(acc_mmm.twabs.get(0).unwrap_or_default().amount, U256::from(0));
+        assert_eq!(acc_mmm.twabs.get(1).unwrap_or_default().amount, U256::from(1000));
+        assert_eq!(acc_mmm.twabs.get(2).unwrap_or_default().amount, U256::from(2500));
+        assert_eq!(acc_mmm.twabs.get(3).unwrap_or_default().amount, U256::from(3000));
 
         assert_eq!(acc_sec.balance, 60);
-        assert_eq!(acc_sec.twabs.get(0).unwrap_or_default().amount, 0);
-        assert_eq!(acc_sec.twabs.get(1).unwrap_or_default().amount, 630);
-        assert_eq!(acc_sec.twabs.get(2).unwrap_or_default().amount, 3270);
+        assert_eq!(acc_sec.twabs.get(0).unwrap_or_default().amount, U256::from(0));
+        assert_eq!(acc_sec.twabs.get(1).unwrap_or_default().amount, U256::from(630));
+        assert_eq!(acc_sec.twabs.get(2).unwrap_or_default().amount, U256::from(3270));
     }
 
     #[test]
@@ -331,4 +421,196 @@ mod tests {
     fn check_total_supply_twabs(){
 
     }
+
+    #[test]
+    fn check_twabs_with_near_balances(){
+        const ONE_NEAR: Balance = 10u128.pow(24);
+        const ONE_YEAR_MS: u64 = 1000 * 3600 * 24 * 365;
+        let mut tickets = AccountsDepositHistory::default();
+
+        // a billion NEAR held for a year is ~3 * 10^43, more than u128::MAX
+        mint(&mut tickets, &mmmm(), 1_000_000_000 * ONE_NEAR, 0);
+        mint(&mut tickets, &sec(), 500_000_000 * ONE_NEAR, ONE_YEAR_MS / 2);
+        burn(&mut tickets, &mmmm(), 600_000_000 * ONE_NEAR, ONE_YEAR_MS);
+        mint(&mut tickets, &sec(), 1, 2 * ONE_YEAR_MS);
+
+        assert!(tickets.accounts.get(&mmmm()).unwrap().twabs.get(1).unwrap().amount > U256::from(u128::MAX));
+        assert_eq!(tickets.average_balance_between_timestamps(&mmmm(), 0, ONE_YEAR_MS), 1_000_000_000 * ONE_NEAR);
+        assert_eq!(tickets.average_balance_between_timestamps(&mmmm(), ONE_YEAR_MS / 2, 3 * ONE_YEAR_MS / 2), 700_000_000 * ONE_NEAR);
+        assert_eq!(tickets.average_balance_between_timestamps(&mmmm(), 0, 3 * ONE_YEAR_MS), 600_000_000 * ONE_NEAR);
+        assert_eq!(tickets.average_balance_between_timestamps(&sec(), 0, 2 * ONE_YEAR_MS), 375_000_000 * ONE_NEAR);
+        assert_eq!(tickets.average_total_supply_between_timestamps(0, 2 * ONE_YEAR_MS), 1_075_000_000 * ONE_NEAR);
+    }
+
+    fn setup_account_with_twabs(tickets: &mut AccountsDepositHistory, acc_id: &AccountId, num_twabs: u64){
+        let mut twabs = TwabBuffer::new([acc_id.as_bytes(), &num_twabs.to_le_bytes()].concat());
+        for idx in 0..num_twabs{
+            // every 100 writes reset the context so the test doesn't run out of gas
+            if idx % 100 == 0 {
+                near_sdk::testing_env!(near_sdk::test_utils::VMContextBuilder::new().build());
+            }
+            twabs.push(&Twab{ amount: U256::from(10 * (idx as u128) * (idx as u128).saturating_sub(1) / 2), timestamp: 10 * idx }, u64::MAX, 0);
+        }
+
+        let balance = AccountBalance{ balance: num_twabs as u128, twabs: twabs };
+        tickets.accounts.insert(acc_id, &balance);
+    }
+
+    fn gas_for_average_balance(tickets: &AccountsDepositHistory, acc_id: &AccountId, start_time: u64, end_time: u64) -> u64{
+        near_sdk::testing_env!(near_sdk::test_utils::VMContextBuilder::new().build());
+        let gas_before = env::used_gas().0;
+        tickets.average_balance_between_timestamps(acc_id, start_time, end_time);
+        return env::used_gas().0 - gas_before;
+    }
+
+    #[test]
+    fn bench_twab_lookup_gas(){
+        let mut tickets = AccountsDepositHistory::default();
+        setup_account_with_twabs(&mut tickets, &mmmm(), 100);
+        setup_account_with_twabs(&mut tickets, &sec(), 5000);
+
+        // the balance between the 37th and the 38th twab is 37
+        assert_eq!(tickets.average_balance_between_timestamps(&sec(), 375, 378), 37);
+        assert_eq!(tickets.average_balance_between_timestamps(&mmmm(), 375, 378), 37);
+
+        let gas_small = gas_for_average_balance(&tickets, &mmmm(), 505, 735);
+        let gas_big = gas_for_average_balance(&tickets, &sec(), 25_005, 37_035);
+
+        // 50 times more twabs, only a few more storage reads
+        assert!(gas_big < 2 * gas_small, "Gas for 5000 twabs {} is not below twice the gas for 100 twabs {}", gas_big, gas_small);
+    }
+
+    #[test]
+    fn check_bounded_twabs(){
+        let mut tickets = setup();
+        let avg_before_pruning = [
+            tickets.average_balance_between_timestamps(&mmmm(), 12, 28),
+            tickets.average_balance_between_timestamps(&mmmm(), 20, 45),
+            tickets.average_total_supply_between_timestamps(20, 50),
+        ];
+
+        // keep the history from time 12
+        tickets.settings.prune_before = 12;
+        tickets.prune_account(&mmmm());
+        tickets.prune_total_supply();
+
+        let acc_mmm : AccountBalance = tickets.accounts.get(&mmmm()).unwrap();
+        assert_eq!(acc_mmm.twabs.len(), 4);
+        assert_eq!(acc_mmm.twabs.get(0).unwrap_or_default().timestamp, 10);
+        assert_eq!(tickets.total_supply.twabs.get(0).unwrap_or_default().timestamp, 10);
+        // averages after the pruned time don't change
+        assert_eq!(tickets.average_balance_between_timestamps(&mmmm(), 12, 28), avg_before_pruning[0]);
+        assert_eq!(tickets.average_balance_between_timestamps(&mmmm(), 20, 45), avg_before_pruning[1]);
+        assert_eq!(tickets.average_total_supply_between_timestamps(20, 50), avg_before_pruning[2]);
+
+        // only the newest twabs are kept, the total supply isn't bounded by cardinality
+        tickets.settings.max_cardinality = 2;
+        tickets.settings.prune_before = 40;
+        mint(&mut tickets, &mmmm(), 10, 60);
+        let acc_mmm : AccountBalance = tickets.accounts.get(&mmmm()).unwrap();
+        assert_eq!(acc_mmm.twabs.len(), 2);
+        assert_eq!(acc_mmm.twabs.get(0).unwrap_or_default().timestamp, 40);
+        assert_eq!(acc_mmm.twabs.get(1).unwrap_or_default().timestamp, 60);
+        assert_eq!(tickets.average_balance_between_timestamps(&mmmm(), 40, 60), 40);
+        assert_eq!(tickets.total_supply.twabs.len(), 7);
+    }
+
+    #[test]
+    fn check_average_across_eviction(){
+        let mut tickets = setup();
+        let avg_before_eviction = tickets.average_balance_between_timestamps(&mmmm(), 30, 50);
+
+        // the twabs after `prune_before` are kept even above the max cardinality
+        tickets.settings.max_cardinality = 2;
+        tickets.settings.prune_before = 30;
+        for time in 60..70{
+            mint(&mut tickets, &mmmm(), 1, time);
+        }
+        let acc_mmm : AccountBalance = tickets.accounts.get(&mmmm()).unwrap();
+        assert_eq!(acc_mmm.twabs.get(0).unwrap_or_default().timestamp, 30);
+        assert_eq!(acc_mmm.twabs.len(), 12);
+        assert_eq!(tickets.average_balance_between_timestamps(&mmmm(), 30, 50), avg_before_eviction);
+        assert_eq!(tickets.balance_at(&mmmm(), 35), 30);
+    }
+
+    #[test]
+    #[should_panic(expected = "The twab history before 10 was removed")]
+    fn check_average_before_evicted_twabs(){
+        let mut tickets = setup();
+        tickets.settings.max_cardinality = 2;
+        tickets.settings.prune_before = 30;
+        mint(&mut tickets, &mmmm(), 10, 60);
+
+        tickets.average_balance_between_timestamps(&mmmm(), 10, 50);
+    }
+
+    #[test]
+    fn check_balances_at(){
+        let setup = setup();
+
+        assert_eq!(setup.balance_at(&mmmm(), 0), 100);
+        assert_eq!(setup.balance_at(&mmmm(), 5), 100);
+        assert_eq!(setup.balance_at(&mmmm(), 10), 150);
+        assert_eq!(setup.balance_at(&mmmm(), 25), 50);
+        assert_eq!(setup.balance_at(&mmmm(), 39), 30);
+        assert_eq!(setup.balance_at(&mmmm(), 100), 40);
+        assert_eq!(setup.balance_at(&sec(), 4), 0);
+        assert_eq!(setup.balance_at(&sec(), 26), 110);
+        assert_eq!(setup.balance_at(&AccountId::new_unchecked("none".to_string()), 26), 0);
+
+        assert_eq!(setup.total_supply_at(0), 100);
+        assert_eq!(setup.total_supply_at(7), 130);
+        assert_eq!(setup.total_supply_at(45), 150);
+        assert_eq!(setup.total_supply_at(50), 100);
+    }
+
+    #[test]
+    fn check_twab_views(){
+        let mut contract = crate::test_utils::get_contract();
+        contract.tickets = setup();
+
+        assert_eq!(contract.get_balance_at(mmmm(), 25).0, 50);
+        assert_eq!(contract.get_total_supply_at(7).0, 130);
+        let averages = contract.get_average_balances_between(mmmm(), vec![(0, 20), (5, 10)]);
+        assert_eq!(averages.iter().map(|el| el.0).collect::<Vec<u128>>(), vec![125, 100]);
+        let twabs = contract.get_account_twabs(mmmm(), 1, 2);
+        assert_eq!(twabs.len(), 2);
+        assert_eq!(twabs[0].amount, "1000");
+        assert_eq!(twabs[1].timestamp, 20);
+        assert!(contract.get_account_twabs(mmmm(), 10, 2).is_empty());
+    }
+
+    #[test]
+    fn check_delegated_twabs(){
+        let mut contract = crate::test_utils::get_contract();
+        let set_context = |timestamp_ms: u64| {
+            near_sdk::testing_env!(near_sdk::test_utils::VMContextBuilder::new()
+                .predecessor_account_id(mmmm())
+                .attached_deposit(1)
+                .block_timestamp(timestamp_ms * 1_000_000)
+                .build());
+        };
+
+        set_context(0);
+        contract.mint_tokens(mmmm(), 100);
+        set_context(10);
+        contract.delegate(sec());
+        assert_eq!(contract.get_delegate(mmmm()), sec());
+        set_context(20);
+        contract.mint_tokens(mmmm(), 50);
+        set_context(30);
+        contract.delegate(mmmm());
+        assert_eq!(contract.get_delegate(mmmm()), mmmm());
+
+        // tickets are not moved by the delegation
+        assert_eq!(contract.tickets.average_balance_between_timestamps(&mmmm(), 0, 10), 100);
+        assert_eq!(contract.tickets.average_balance_between_timestamps(&mmmm(), 20, 30), 150);
+        // odds are
+        assert_eq!(contract.delegated_tickets.average_balance_between_timestamps(&mmmm(), 0, 10), 100);
+        assert_eq!(contract.delegated_tickets.average_balance_between_timestamps(&mmmm(), 10, 30), 0);
+        assert_eq!(contract.delegated_tickets.average_balance_between_timestamps(&sec(), 10, 20), 100);
+        assert_eq!(contract.delegated_tickets.average_balance_between_timestamps(&sec(), 20, 30), 150);
+        assert_eq!(contract.delegated_tickets.accounts.get(&sec()).unwrap().balance, 0);
+        assert_eq!(contract.delegated_tickets.accounts.get(&mmmm()).unwrap().balance, 150);
+    }
 }
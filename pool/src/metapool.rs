@@ -0,0 +1,357 @@
+use crate::*;
+use near_sdk::{AccountId, Balance, json_types::{U128}, PromiseError, Promise};
+use crate::interfaces::defi::IYieldSource;
+use crate::utils::gas;
+use near_sdk::ext_contract;
+use common::types::U256;
+
+/// The highest liquid unstake fee that is covered when unstaking, in basis points
+const MAX_LIQUID_UNSTAKE_FEE_BPS: u128 = 300;
+const BPS_NOMINAL: u128 = 10_000;
+
+#[ext_contract(ext_metapool)]
+pub trait ExtMetapool {
+    fn deposit_and_stake(&mut self);
+    fn liquid_unstake(&mut self, st_near_to_burn: U128, min_expected_near: U128) -> LiquidUnstakeResult;
+    fn get_account_info(&self, account_id: AccountId) -> GetAccountInfoResult;
+    fn get_st_near_price(&self) -> U128;
+}
+
+#[ext_contract(ext_wrap_near)]
+pub trait ExtWrapNear {
+    fn near_deposit(&mut self);
+    fn near_withdraw(&mut self, amount: U128);
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LiquidUnstakeResult{
+    pub near: U128,
+    pub fee: U128,
+    pub meta: U128,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GetAccountInfoResult{
+    pub account_id: AccountId,
+    /// The stNEAR of the account
+    pub st_near: U128,
+    /// The stNEAR of the account valued in NEAR
+    pub valued_st_near: U128,
+}
+
+/// Stakes the deposited wNEAR in Meta Pool. The wNEAR is unwrapped before staking,
+/// the NEAR that is liquid unstaked is wrapped back before it is sent
+pub (crate) struct MetapoolYieldSource{
+    pub(crate) address: AccountId,
+}
+
+impl MetapoolYieldSource{
+    fn source(&self) -> YieldSource{
+        return YieldSource::Metapool { address: self.address.clone() };
+    }
+}
+
+/// The stNEAR to burn so that after the liquid unstake fee at least `amount` NEAR is received,
+/// at most all the stNEAR of the pool
+pub (crate) fn st_near_to_burn(amount: Balance, account: &GetAccountInfoResult) -> Balance{
+    if account.valued_st_near.0 == 0{
+        return account.st_near.0;
+    }
+
+    let value = U256::from(amount) * U256::from(BPS_NOMINAL) / U256::from(BPS_NOMINAL - MAX_LIQUID_UNSTAKE_FEE_BPS);
+    let valued_st_near = U256::from(account.valued_st_near.0);
+    let st_near = ((value * U256::from(account.st_near.0) + valued_st_near - 1) / valued_st_near).as_u128();
+    return std::cmp::min(st_near, account.st_near.0);
+}
+
+/// The NEAR that burning `st_near` brings at least, after the highest covered fee. It is `amount`
+/// unless the stNEAR of the pool is worth less than `amount` with the fee
+pub (crate) fn min_expected_near(amount: Balance, st_near: Balance, account: &GetAccountInfoResult) -> Balance{
+    if account.st_near.0 == 0{
+        return 0;
+    }
+
+    let value = U256::from(st_near) * U256::from(account.valued_st_near.0) / U256::from(account.st_near.0);
+    let min_near = (value * U256::from(BPS_NOMINAL - MAX_LIQUID_UNSTAKE_FEE_BPS) / U256::from(BPS_NOMINAL)).as_u128();
+    return std::cmp::min(amount, min_near);
+}
+
+#[near_bindgen]
+impl Contract{
+    #[private]
+    pub fn on_near_unwrapped_stake(&mut self, metapool: AccountId, amount: Balance, #[callback_result] result: Result<(), PromiseError>) -> Promise{
+        if result.is_err(){
+            panic!("Error when unwrapping {} wNEAR", amount);
+        }
+
+        return ext_metapool::deposit_and_stake(metapool.clone(), amount, gas::GAS_FOR_METAPOOL_STAKE)
+            .then(crate::this_contract::on_near_staked(metapool, amount, env::current_account_id(), 0, gas::GAS_FOR_AFTER_METAPOOL_STAKE));
+    }
+
+    /// Returns the staked amount. Meta Pool refunds the NEAR of a failed stake,
+    /// it is wrapped back before the deposit counts it as not used
+    #[private]
+    pub fn on_near_staked(&mut self, metapool: AccountId, amount: Balance, #[callback_result] result: Result<(), PromiseError>) -> PromiseOrValue<U128>{
+        if result.is_ok(){
+            return PromiseOrValue::Value(U128(amount));
+        }
+
+        log!("Error when staking {} NEAR, wrapping the refunded NEAR", amount);
+        return PromiseOrValue::Promise(ext_wrap_near::near_deposit(self.deposited_token_id.clone(), amount, gas::GAS_FOR_METAPOOL_CALL)
+            .then(crate::this_contract::on_refunded_near_wrapped(metapool, amount, env::current_account_id(), 0, gas::GAS_FOR_AFTER_NEAR_WRAP)));
+    }
+
+    /// Returns 0 once the refunded NEAR is wrapped, so the deposit counts it as idle wNEAR.
+    /// The NEAR that failed to be wrapped is kept for `wrap_unstaked_near` and taken out
+    /// of the allocation of Meta Pool here, so the deposit doesn't count it as idle
+    #[private]
+    pub fn on_refunded_near_wrapped(&mut self, metapool: AccountId, amount: Balance, #[callback_result] result: Result<(), PromiseError>) -> U128{
+        if result.is_ok(){
+            return U128(0);
+        }
+
+        log!("Error when wrapping {} refunded NEAR, it is kept unwrapped in the pool", amount);
+        self.yield_sources.unwrapped_near += amount;
+        self.yield_sources.debit(&YieldSource::Metapool { address: metapool }, amount);
+        return U128(amount);
+    }
+
+    #[private]
+    pub fn on_get_account_info_unstake(&mut self, metapool: AccountId, amount: Balance, #[callback_result] call_result: Result<GetAccountInfoResult, PromiseError>) -> Promise{
+        if call_result.is_err(){
+            panic!("Error when getting the stNEAR of the pool");
+        }
+
+        let account = call_result.unwrap();
+        let st_near = st_near_to_burn(amount, &account);
+        let min_expected_near = min_expected_near(amount, st_near, &account);
+        return ext_metapool::liquid_unstake(U128(st_near), U128(min_expected_near), metapool, 0, gas::GAS_FOR_LIQUID_UNSTAKE)
+            .then(crate::this_contract::on_liquid_unstaked(amount, env::current_account_id(), 0, gas::GAS_FOR_AFTER_LIQUID_UNSTAKE));
+    }
+
+    /// Wraps all the unstaked NEAR. It is more than `amount` when the fee is lower than the covered fee,
+    /// less when all the stNEAR was burned and didn't cover the fee
+    #[private]
+    pub fn on_liquid_unstaked(&mut self, amount: Balance, #[callback_result] call_result: Result<LiquidUnstakeResult, PromiseError>) -> Promise{
+        if call_result.is_err(){
+            panic!("Error when liquid unstaking {} NEAR", amount);
+        }
+
+        let near = call_result.unwrap().near.0;
+        if near < amount{
+            log!("Only {} of {} NEAR is unstaked, the stNEAR of the pool didn't cover the fee", near, amount);
+        }
+
+        return ext_wrap_near::near_deposit(self.deposited_token_id.clone(), near, gas::GAS_FOR_METAPOOL_CALL)
+            .then(crate::this_contract::on_unstaked_near_wrapped(near, env::current_account_id(), 0, gas::GAS_FOR_AFTER_NEAR_WRAP));
+    }
+
+    /// Returns the wNEAR that the redeem brought to the pool. The stNEAR is already burned,
+    /// so the NEAR that failed to be wrapped is kept for `wrap_unstaked_near` and nothing is redeemed
+    #[private]
+    pub fn on_unstaked_near_wrapped(&mut self, amount: Balance, #[callback_result] result: Result<(), PromiseError>) -> U128{
+        if result.is_err(){
+            log!("Error when wrapping {} NEAR, it is kept unwrapped in the pool", amount);
+            self.yield_sources.unwrapped_near += amount;
+            return U128(0);
+        }
+
+        return U128(amount);
+    }
+
+    pub fn get_unwrapped_near(&self) -> U128{
+        return U128(self.yield_sources.unwrapped_near);
+    }
+
+    /// Wraps the NEAR that failed to be wrapped after Meta Pool sent it, the wNEAR becomes idle
+    pub fn wrap_unstaked_near(&mut self) -> Promise{
+        self.assert_role(Role::Keeper);
+        self.assert_not_paused(Operation::YieldTransfer);
+        let amount = self.yield_sources.unwrapped_near;
+        assert!(amount > 0, "There is no unwrapped NEAR");
+        self.yield_sources.unwrapped_near = 0;
+
+        return ext_wrap_near::near_deposit(self.deposited_token_id.clone(), amount, gas::GAS_FOR_METAPOOL_CALL)
+            .then(crate::this_contract::on_near_wrapped(amount, env::current_account_id(), 0, gas::GAS_FOR_AFTER_NEAR_WRAP));
+    }
+
+    #[private]
+    pub fn on_near_wrapped(&mut self, amount: Balance, #[callback_result] result: Result<(), PromiseError>){
+        if result.is_err(){
+            log!("Error when wrapping {} NEAR", amount);
+            self.yield_sources.unwrapped_near += amount;
+            return;
+        }
+
+        self.yield_sources.idle += amount;
+    }
+
+    /// Returns the whole pool position in Meta Pool
+    #[private]
+    pub fn on_get_balance_from_metapool(&self, #[callback_result] call_result: Result<GetAccountInfoResult, PromiseError>) -> Balance{
+        if call_result.is_err(){
+            panic!("Error when getting the balance from Meta Pool");
+        }
+
+        return call_result.unwrap().valued_st_near.0;
+    }
+
+    #[private]
+    pub fn on_get_reward_from_metapool(&self, #[callback_result] call_result: Result<GetAccountInfoResult, PromiseError>) -> Balance{
+        if call_result.is_err(){
+            panic!("Error when getting the reward from Meta Pool");
+        }
+
+        return self.get_reward_for_position(call_result.unwrap().valued_st_near.0);
+    }
+}
+
+impl IYieldSource for MetapoolYieldSource{
+    fn get_reward(&self, account_id: &AccountId) -> Promise {
+        ext_metapool::get_account_info(account_id.clone(), self.address.clone(), 0, gas::GAS_FOR_METAPOOL_CALL)
+         .then(crate::this_contract::on_get_reward_from_metapool(env::current_account_id(), 0, gas::GET_BALANCE_FROM_DEFI))
+    }
+
+    fn get_balance(&self, account_id: &AccountId) -> Promise {
+        ext_metapool::get_account_info(account_id.clone(), self.address.clone(), 0, gas::GAS_FOR_METAPOOL_CALL)
+         .then(crate::this_contract::on_get_balance_from_metapool(env::current_account_id(), 0, gas::GET_BALANCE_FROM_DEFI))
+    }
+
+    fn transfer(&self, token_id: &AccountId, amount: Balance) -> Promise {
+        log!("Stake {} {} in {}", amount, token_id, self.address);
+
+        ext_wrap_near::near_withdraw(U128(amount), token_id.clone(), 1, gas::GAS_FOR_METAPOOL_CALL)
+        .then(crate::this_contract::on_near_unwrapped_stake(self.address.clone(), amount, env::current_account_id(), 0, gas::GAS_FOR_AFTER_NEAR_UNWRAP))
+    }
+
+    /// Liquid unstakes stNEAR worth `amount` NEAR after the covered fee and wraps all of the unstaked NEAR.
+    /// Returns the wrapped amount
+    fn redeem(&self, _token_id: &AccountId, amount: Balance) -> Promise {
+        ext_metapool::get_account_info(env::current_account_id(), self.address.clone(), 0, gas::GAS_FOR_METAPOOL_CALL)
+        .then(crate::this_contract::on_get_account_info_unstake(self.address.clone(), amount, env::current_account_id(), 0, gas::GAS_FOR_METAPOOL_UNSTAKE))
+    }
+
+    fn withdraw(&self, account_id: &AccountId, token_id: &AccountId, amount: Balance) -> Promise {
+        self.redeem(token_id, amount)
+        .then(crate::this_contract::on_withdraw_from_defi(account_id.clone(), amount, self.source(), env::current_account_id(), 0, gas::GAS_FOR_AFTER_WITHDRAW_FROM_DEFI))
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use crate::*;
+    use crate::test_utils::{get_contract, mmmm, set_context, set_promise_results, returned};
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use crate::yield_sources::WeightedYieldSource;
+    use super::{st_near_to_burn, min_expected_near, GetAccountInfoResult};
+
+    fn account_info(st_near: Balance, valued_st_near: Balance) -> GetAccountInfoResult{
+        return GetAccountInfoResult { account_id: mmmm(), st_near: U128(st_near), valued_st_near: U128(valued_st_near) };
+    }
+
+    #[test]
+    fn test_st_near_to_burn(){
+        // 3% of the value is the highest covered fee
+        assert_eq!(st_near_to_burn(97, &account_info(1000, 1000)), 100);
+        assert_eq!(st_near_to_burn(97, &account_info(1000, 2000)), 50);
+        // rounded up, so the value never falls below the amount
+        assert_eq!(st_near_to_burn(97, &account_info(1000, 3000)), 34);
+        assert_eq!(min_expected_near(97, 34, &account_info(1000, 3000)), 97);
+    }
+
+    #[test]
+    fn test_st_near_to_burn_capped_at_balance(){
+        // the whole position is redeemed, the fee is taken from it
+        assert_eq!(st_near_to_burn(200, &account_info(100, 200)), 100);
+        assert_eq!(min_expected_near(200, 100, &account_info(100, 200)), 194);
+        // the last 3% of the position
+        assert_eq!(st_near_to_burn(99, &account_info(100, 100)), 100);
+        assert_eq!(min_expected_near(99, 100, &account_info(100, 100)), 97);
+        assert_eq!(st_near_to_burn(10, &account_info(0, 0)), 0);
+    }
+
+    #[test]
+    fn test_reward_from_metapool(){
+        let mut contract = get_contract();
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(mmmm())
+            .predecessor_account_id(mmmm())
+            .build());
+        contract.accounting.on_deposit(100);
+
+        assert_eq!(contract.on_get_balance_from_metapool(Ok(account_info(90, 130))), 130);
+        assert_eq!(contract.on_get_reward_from_metapool(Ok(account_info(90, 130))), 30);
+        assert_eq!(contract.on_get_reward_from_metapool(Ok(account_info(90, 99))), 0);
+    }
+
+    #[test]
+    fn test_unstaked_near_wrap_failed(){
+        let mut contract = get_contract();
+        set_context(mmmm());
+        contract.accounting.on_deposit(100);
+
+        assert_eq!(contract.on_unstaked_near_wrapped(40, Ok(())), U128(40));
+        // the stNEAR is burned, nothing is redeemed and the NEAR is kept for the keeper
+        assert_eq!(contract.on_unstaked_near_wrapped(60, Err(PromiseError::Failed)), U128(0));
+        assert_eq!(contract.get_unwrapped_near(), U128(60));
+        assert_eq!(contract.get_yield_sources_balance_result(), Some(60));
+
+        contract.wrap_unstaked_near();
+        assert_eq!(contract.get_unwrapped_near(), U128(0));
+        contract.on_near_wrapped(60, Err(PromiseError::Failed));
+        assert_eq!(contract.get_unwrapped_near(), U128(60));
+        assert_eq!(contract.yield_sources.idle, 0);
+
+        set_context(mmmm());
+        contract.wrap_unstaked_near();
+        contract.on_near_wrapped(60, Ok(()));
+        assert_eq!(contract.get_unwrapped_near(), U128(0));
+        assert_eq!(contract.yield_sources.idle, 60);
+    }
+
+    #[test]
+    fn test_refunded_stake_is_wrapped_before_idle(){
+        let mut contract = get_contract();
+        let metapool = YieldSource::Metapool { address: mmmm() };
+        contract.yield_sources.sources = vec![WeightedYieldSource { source: metapool.clone(), weight: 100, allocated: 100, unpaid_yield: 0 }];
+        set_context(mmmm());
+
+        assert!(matches!(contract.on_near_staked(mmmm(), 100, Ok(())), PromiseOrValue::Value(U128(100))));
+        assert!(matches!(contract.on_near_staked(mmmm(), 100, Err(PromiseError::Failed)), PromiseOrValue::Promise(_)));
+
+        // the refunded NEAR is wrapped, the deposit keeps it as idle wNEAR
+        assert_eq!(contract.on_refunded_near_wrapped(mmmm(), 100, Ok(())), U128(0));
+        set_promise_results(vec![returned(0)]);
+        contract.on_deposited_to_yield_source(metapool.clone(), 100);
+        assert_eq!(contract.yield_sources.idle, 100);
+        assert_eq!(contract.yield_sources.sources[0].allocated, 0);
+    }
+
+    #[test]
+    fn test_refunded_stake_wrap_failed(){
+        let mut contract = get_contract();
+        let metapool = YieldSource::Metapool { address: mmmm() };
+        contract.yield_sources.sources = vec![WeightedYieldSource { source: metapool.clone(), weight: 100, allocated: 100, unpaid_yield: 0 }];
+        set_context(mmmm());
+
+        // the NEAR is neither idle nor in Meta Pool
+        assert_eq!(contract.on_refunded_near_wrapped(mmmm(), 100, Err(PromiseError::Failed)), U128(100));
+        set_promise_results(vec![returned(100)]);
+        contract.on_deposited_to_yield_source(metapool, 100);
+        assert_eq!(contract.yield_sources.idle, 0);
+        assert_eq!(contract.yield_sources.sources[0].allocated, 0);
+        assert_eq!(contract.get_unwrapped_near(), U128(100));
+    }
+
+    #[test]
+    #[should_panic(expected = "There is no unwrapped NEAR")]
+    fn test_wrap_unstaked_near_without_near(){
+        let mut contract = get_contract();
+        set_context(mmmm());
+        contract.wrap_unstaked_near();
+    }
+}
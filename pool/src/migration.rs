@@ -0,0 +1,315 @@
+use crate::*;
+use crate::events::events;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum MigrationStage{
+    /// Everything is being taken out of the old yield source
+    Redeeming,
+    /// The new yield source is active and the amount, kept idle, still has to be deposited in it
+    Depositing { amount: U128 },
+}
+
+/// Moves the whole position of the pool from one yield source to another.
+/// While it is in progress, the yield transfers are paused
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct YieldSourceMigration{
+    pub old_source: YieldSource,
+    pub new_source: YieldSource,
+    pub stage: MigrationStage,
+}
+
+#[near_bindgen]
+impl Contract{
+    pub fn get_yield_source_migration(&self) -> Option<YieldSourceMigration>{
+        return self.migration.clone();
+    }
+
+    /// Takes everything out of `old_source` and deposits it in `new_source`, which takes over its weight.
+    /// If the redeem fails, the old yield source stays active.
+    /// If the deposit fails, the tokens stay idle in the pool until `resume_yield_source_migration`
+    #[payable]
+    pub fn migrate_yield_source(&mut self, old_source: YieldSource, new_source: YieldSource) -> Promise{
+        assert_one_yocto();
+        self.assert_role(Role::Admin);
+        assert!(self.migration.is_none(), "A yield source migration is in progress");
+        assert!(self.yield_sources.pending_redeems == 0, "A redeem from the yield sources is in progress");
+        assert!(self.yield_sources.find(&old_source).is_some(), "There is no such yield source");
+        assert!(self.yield_sources.find(&new_source).is_none(), "The yield source is already active");
+
+        events::yield_source_migration_started(&old_source, &new_source);
+        self.migration = Some(YieldSourceMigration {
+            old_source: old_source.clone(),
+            new_source: new_source,
+            stage: MigrationStage::Redeeming,
+        });
+
+        return self
+            .get_yield_source(&old_source)
+            .get_balance(&env::current_account_id())
+            .then(this_contract::on_get_balance_redeem_for_migration(env::current_account_id(), 0, gas::GAS_FOR_MIGRATION_REDEEM));
+    }
+
+    /// Retries the deposit in the new yield source after it failed
+    #[payable]
+    pub fn resume_yield_source_migration(&mut self) -> Promise{
+        assert_one_yocto();
+        self.assert_role(Role::Admin);
+
+        let amount = match self.migration.as_ref().map(|el| el.stage.clone()){
+            Some(MigrationStage::Depositing { amount }) => amount.0,
+            _ => panic!("There is no migration waiting for a deposit"),
+        };
+
+        return self.deposit_for_migration(amount);
+    }
+
+    #[private]
+    pub fn on_get_balance_redeem_for_migration(&mut self, #[callback_result] call_result: Result<Balance, PromiseError>) -> PromiseOrValue<()>{
+        let balance = match call_result{
+            Ok(balance) => balance,
+            Err(_) => {
+                self.abort_migration("Error when getting the balance from the yield source");
+                return PromiseOrValue::Value(());
+            }
+        };
+
+        if balance == 0{
+            self.switch_yield_source();
+            self.complete_migration(0);
+            return PromiseOrValue::Value(());
+        }
+
+        let old_source = self.migration.as_ref().expect("There is no yield source migration").old_source.clone();
+        self.yield_sources.start_redeem();
+        let promise = self
+            .get_yield_source(&old_source)
+            .redeem(&self.deposited_token_id, balance)
+            .then(this_contract::on_redeemed_for_migration(balance, env::current_account_id(), 0, gas::GAS_FOR_MIGRATION_DEPOSIT));
+
+        return PromiseOrValue::Promise(promise);
+    }
+
+    /// Deposits the redeemed tokens in the new yield source. After the unstake fee,
+    /// Meta Pool redeems less than its balance
+    #[private]
+    pub fn on_redeemed_for_migration(&mut self, amount: Balance) -> PromiseOrValue<()>{
+        self.yield_sources.end_redeem();
+        let redeemed = match utils::utils::redeemed_amount(amount){
+            Some(redeemed) => redeemed,
+            None => {
+                self.abort_migration("Error when redeeming from the old yield source");
+                return PromiseOrValue::Value(());
+            }
+        };
+
+        // the unpaid yield stays idle to pay the prizes of the old yield source
+        let unpaid_yield = std::cmp::min(self.switch_yield_source(), redeemed);
+        self.yield_sources.idle += redeemed;
+        if redeemed == unpaid_yield{
+            self.complete_migration(0);
+            return PromiseOrValue::Value(());
+        }
+
+        return PromiseOrValue::Promise(self.deposit_for_migration(redeemed - unpaid_yield));
+    }
+
+    #[private]
+    pub fn on_deposited_for_migration(&mut self, amount: Balance){
+        let deposited = utils::utils::deposited_amount(amount);
+
+        if deposited >= amount{
+            self.complete_migration(amount);
+            return;
+        }
+
+        let remaining = amount - deposited;
+        log!("{} is not deposited in the new yield source", remaining);
+        events::yield_source_migration_deposit_failed(remaining);
+        if let Some(migration) = self.migration.as_mut(){
+            migration.stage = MigrationStage::Depositing { amount: U128(remaining) };
+            self.yield_sources.debit(&migration.new_source, remaining);
+            self.yield_sources.idle += remaining;
+        }
+    }
+
+    /// Moves `amount` out of the idle tokens into the allocation of the new yield source
+    fn deposit_for_migration(&mut self, amount: Balance) -> Promise{
+        let migration = self.migration.as_mut().expect("There is no yield source migration");
+        migration.stage = MigrationStage::Depositing { amount: U128(amount) };
+        let new_source = migration.new_source.clone();
+        self.yield_sources.idle -= amount;
+        self.yield_sources.credit(&new_source, amount);
+
+        return self
+            .get_yield_source(&new_source)
+            .transfer(&self.deposited_token_id, amount)
+            .then(this_contract::on_deposited_for_migration(amount, env::current_account_id(), 0, gas::GAS_FOR_AFTER_DEPOSIT_TO_YIELD_SOURCE));
+    }
+
+    /// The new yield source keeps the weight of the old one, it is allocated only what is deposited in it.
+    /// Returns the unpaid yield of the old yield source
+    fn switch_yield_source(&mut self) -> Balance{
+        let migration = self.migration.as_ref().expect("There is no yield source migration");
+        let idx = self.yield_sources.find(&migration.old_source).expect("There is no such yield source");
+        let unpaid_yield = self.yield_sources.sources[idx].unpaid_yield;
+        self.yield_sources.sources[idx].source = migration.new_source.clone();
+        self.yield_sources.sources[idx].allocated = 0;
+        self.yield_sources.sources[idx].unpaid_yield = 0;
+        return unpaid_yield;
+    }
+
+    fn abort_migration(&mut self, reason: &str){
+        log!("{}", reason);
+        if let Some(migration) = self.migration.take(){
+            events::yield_source_migration_aborted(&migration.old_source, &migration.new_source);
+        }
+    }
+
+    fn complete_migration(&mut self, amount: Balance){
+        if let Some(migration) = self.migration.take(){
+            events::yield_source_migration_completed(&migration.old_source, &migration.new_source, amount);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use crate::*;
+    use crate::test_utils::{get_contract, mmmm, sec, burrow, set_context, set_promise_results, returned};
+    use near_sdk::PromiseResult;
+
+    use super::{MigrationStage, YieldSourceMigration};
+
+    fn metapool() -> YieldSource{
+        return YieldSource::Metapool { address: AccountId::new_unchecked("metapool".to_string()) };
+    }
+
+    fn burrow_source() -> YieldSource{
+        return YieldSource::Burrow { address: burrow() };
+    }
+
+    #[test]
+    fn test_migrate_yield_source(){
+        let mut contract = get_contract();
+        contract.yield_sources.sources[0].allocated = 90;
+        set_context(mmmm());
+        contract.migrate_yield_source(burrow_source(), metapool());
+        assert!(contract.is_paused(Operation::YieldTransfer));
+
+        set_context(mmmm());
+        contract.on_get_balance_redeem_for_migration(Ok(100));
+        assert_eq!(contract.yield_sources.sources[0].source, burrow_source());
+
+        // the yield is redeemed too, the new yield source is allocated what is deposited
+        set_promise_results(vec![PromiseResult::Successful(vec![])]);
+        contract.on_redeemed_for_migration(100);
+        assert_eq!(contract.yield_sources.sources[0].source, metapool());
+        assert_eq!(contract.yield_sources.sources[0].allocated, 100);
+        assert_eq!(contract.get_idle_balance(), U128(0));
+
+        // only part of the amount is used, the rest is idle
+        set_promise_results(vec![returned(60)]);
+        contract.on_deposited_for_migration(100);
+        assert_eq!(
+            contract.get_yield_source_migration().unwrap().stage,
+            MigrationStage::Depositing { amount: U128(40) }
+        );
+        assert_eq!(contract.yield_sources.sources[0].allocated, 60);
+        assert_eq!(contract.get_idle_balance(), U128(40));
+
+        set_context(mmmm());
+        contract.resume_yield_source_migration();
+        set_promise_results(vec![PromiseResult::Successful(vec![])]);
+        contract.on_deposited_for_migration(40);
+        assert_eq!(contract.get_yield_source_migration(), None);
+        assert!(!contract.is_paused(Operation::YieldTransfer));
+        assert_eq!(contract.yield_sources.sources[0].allocated, 100);
+        assert_eq!(contract.get_idle_balance(), U128(0));
+    }
+
+    #[test]
+    fn test_migrate_from_metapool_to_burrow(){
+        let mut contract = get_contract();
+        contract.yield_sources = YieldSources::new(metapool());
+        contract.yield_sources.sources[0].allocated = 100;
+        set_context(mmmm());
+        contract.migrate_yield_source(metapool(), burrow_source());
+
+        // the whole balance is redeemed, the unstake fee is taken from it
+        set_context(mmmm());
+        contract.on_get_balance_redeem_for_migration(Ok(100));
+        set_promise_results(vec![returned(97)]);
+        contract.on_redeemed_for_migration(100);
+        assert_eq!(contract.yield_sources.sources[0].source, burrow_source());
+        assert_eq!(
+            contract.get_yield_source_migration().unwrap().stage,
+            MigrationStage::Depositing { amount: U128(97) }
+        );
+
+        set_promise_results(vec![PromiseResult::Successful(vec![])]);
+        contract.on_deposited_for_migration(97);
+        assert_eq!(contract.get_yield_source_migration(), None);
+        assert_eq!(contract.yield_sources.pending_redeems, 0);
+        assert_eq!(contract.yield_sources.sources[0].allocated, 97);
+    }
+
+    #[test]
+    fn test_migration_keeps_unpaid_yield_idle(){
+        let mut contract = get_contract();
+        contract.yield_sources.sources[0].allocated = 90;
+        contract.yield_sources.sources[0].unpaid_yield = 10;
+        set_context(mmmm());
+        contract.migrate_yield_source(burrow_source(), metapool());
+
+        set_context(mmmm());
+        contract.on_get_balance_redeem_for_migration(Ok(100));
+        set_promise_results(vec![PromiseResult::Successful(vec![])]);
+        contract.on_redeemed_for_migration(100);
+        assert_eq!(contract.yield_sources.sources[0].allocated, 90);
+        assert_eq!(contract.yield_sources.sources[0].unpaid_yield, 0);
+        assert_eq!(contract.get_idle_balance(), U128(10));
+    }
+
+    #[test]
+    fn test_migration_redeem_failed(){
+        let mut contract = get_contract();
+        set_context(mmmm());
+        contract.migrate_yield_source(burrow_source(), metapool());
+
+        set_context(mmmm());
+        contract.on_get_balance_redeem_for_migration(Ok(100));
+        set_promise_results(vec![PromiseResult::Failed]);
+        contract.on_redeemed_for_migration(100);
+        assert_eq!(contract.get_yield_source_migration(), None::<YieldSourceMigration>);
+        assert_eq!(contract.yield_sources.sources[0].source, burrow_source());
+    }
+
+    #[test]
+    #[should_panic(expected = "A yield source migration is in progress")]
+    fn test_migration_in_progress(){
+        let mut contract = get_contract();
+        set_context(mmmm());
+        contract.migrate_yield_source(burrow_source(), metapool());
+        set_context(mmmm());
+        contract.migrate_yield_source(metapool(), YieldSource::Burrow { address: sec() });
+    }
+
+    #[test]
+    #[should_panic(expected = "A redeem from the yield sources is in progress")]
+    fn test_migration_with_pending_redeem(){
+        let mut contract = get_contract();
+        contract.yield_sources.start_redeem();
+        set_context(mmmm());
+        contract.migrate_yield_source(burrow_source(), metapool());
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller doesn't have the Admin role")]
+    fn test_migration_not_admin(){
+        let mut contract = get_contract();
+        set_context(sec());
+        contract.migrate_yield_source(burrow_source(), metapool());
+    }
+}
@@ -0,0 +1,102 @@
+use crate::*;
+
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Operation{
+    /// Minting tickets for deposited tokens in `ft_on_transfer`
+    Deposit,
+    /// Claiming prizes
+    Claim,
+    /// Calculating the picks of an account for a draw
+    GetPicks,
+    /// Every transfer of tokens to and from the yield source
+    YieldTransfer,
+}
+
+#[near_bindgen]
+impl Contract{
+    /// The yield transfers are also paused while the yield source is migrated,
+    /// every operation while the accounts of the first version are migrated
+    pub (crate) fn is_operation_paused(&self, operation: Operation) -> bool{
+        return self.paused_operations.contains(&operation) ||
+            (operation == Operation::YieldTransfer && self.migration.is_some()) ||
+            self.tickets_migration.is_some();
+    }
+
+    pub (crate) fn assert_not_paused(&self, operation: Operation){
+        assert!(!self.is_operation_paused(operation), "{:?} is paused", operation);
+    }
+
+    pub fn is_paused(&self, operation: Operation) -> bool{
+        return self.is_operation_paused(operation);
+    }
+
+    pub fn get_paused_operations(&self) -> Vec<Operation>{
+        return self.paused_operations.clone();
+    }
+
+    #[payable]
+    pub fn pause(&mut self, operations: Vec<Operation>){
+        assert_one_yocto();
+        self.assert_role(Role::Pauser);
+
+        for operation in operations{
+            if !self.paused_operations.contains(&operation){
+                self.paused_operations.push(operation);
+                events::events::paused(operation, &env::predecessor_account_id());
+            }
+        }
+    }
+
+    /// Pausers can stop the operations, but only admins can resume them
+    #[payable]
+    pub fn unpause(&mut self, operations: Vec<Operation>){
+        assert_one_yocto();
+        self.assert_role(Role::Admin);
+
+        for operation in operations{
+            if self.paused_operations.contains(&operation){
+                self.paused_operations.retain(|el| *el != operation);
+                events::events::unpaused(operation, &env::predecessor_account_id());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use crate::*;
+    use crate::pause::Operation;
+    use crate::test_utils::{get_contract, mmmm, sec, usdc, set_caller};
+
+    #[test]
+    fn test_paused_deposit_is_refunded(){
+        let mut contract = get_contract();
+        set_caller(mmmm());
+        contract.pause(vec![Operation::Deposit]);
+        assert!(contract.is_paused(Operation::Deposit));
+        assert!(!contract.is_paused(Operation::Claim));
+
+        set_caller(usdc());
+        let result = contract.ft_on_transfer(sec(), U128(10), "".to_string());
+        assert!(matches!(result, PromiseOrValue::Value(U128(10))));
+        assert_eq!(contract.token.accounts.get(&sec()), None);
+
+        set_caller(mmmm());
+        contract.unpause(vec![Operation::Deposit]);
+        assert!(contract.get_paused_operations().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller doesn't have the Admin role")]
+    fn test_pauser_cannot_unpause(){
+        let mut contract = get_contract();
+        set_caller(mmmm());
+        contract.grant_role(Role::Pauser, sec());
+
+        set_caller(sec());
+        contract.pause(vec![Operation::Claim, Operation::GetPicks]);
+        assert_eq!(contract.get_paused_operations(), vec![Operation::Claim, Operation::GetPicks]);
+        contract.unpause(vec![Operation::Claim]);
+    }
+}
@@ -1,6 +1,6 @@
 use crate::interfaces::{picker::{Picker}, prize_distribution::PrizeDistributionActor};
 use near_sdk::{collections::{UnorderedMap}};
-use common::types::{DrawId, NumPicks};
+use common::types::{DrawId, NumPicks, U256};
 use utils::storage_keys::StorageKeys;
 use crate::*;
 
@@ -59,10 +59,10 @@ impl Contract{
         let draw = call_result.unwrap();
         log!("{:?}", draw);
         
-        let acc_tickets = self.tickets.average_balance_between_timestamps(&account_id, draw.started_at, draw.completed_at);
+        let acc_tickets = self.delegated_tickets.average_balance_between_timestamps(&account_id, draw.started_at, draw.completed_at);
         let total_tickets = self.tickets.average_total_supply_between_timestamps(draw.started_at, draw.completed_at);
         let prize_distribution = self.get_prize_distribution(draw.draw_id);
-        let acc_picks: NumPicks = (prize_distribution.number_of_picks as u128) * acc_tickets / total_tickets;
+        let acc_picks: NumPicks = (U256::from(prize_distribution.number_of_picks) * U256::from(acc_tickets) / U256::from(total_tickets)).as_u128();
         self.acc_picks.add_picks_for_draw(&account_id, &draw.draw_id, acc_picks);
 
         return acc_picks;
@@ -72,6 +72,7 @@ impl Contract{
 #[near_bindgen]
 impl Picker for Contract{
     fn get_picks(&self, draw_id: DrawId) -> PromiseOrValue<NumPicks> {
+        self.assert_not_paused(Operation::GetPicks);
         let caller = env::signer_account_id();
         let acc_draws_picks = self.acc_picks.get_draws(&caller);
 
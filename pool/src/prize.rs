@@ -1,6 +1,7 @@
 use crate::*;
 use near_sdk::{borsh::{self, BorshDeserialize, BorshSerialize}};
-use crate::interfaces::prize_distribution::{PrizeDistribution, PrizeDistributionActor};
+use crate::interfaces::prize_distribution::{PrizeDistribution, PrizeDistributionActor, PrizeConfig, PickPrizeView, MAX_TIERS};
+use crate::events::events;
 use common::{generic_ring_buffer::{GenericRingBuffer, RingBuffer}, types::{WinningNumber, U256}};
 
 const MAX_PRIZES_CAPACITY: usize = 32;
@@ -10,6 +11,64 @@ const TIERS: [u32; 16]= [20,30,20,10,5,5,10,0,0,0,0,0,0,0,0,0];
 const TIERS_NOMINAL:u128 = 100;
 const PRIZE_DISTRIBUTION_TIME_OFFSET: u64 = 1000 * 3600 * 24 * 7;
 
+impl Default for PrizeConfig{
+    fn default() -> Self {
+        return Self {
+            tiers: TIERS,
+            tiers_nominal: TIERS_NOMINAL,
+            bit_range_size: BIT_RANGE_SIZE,
+            max_cardinality: (MAX_TIERS - 1) as u8,
+            min_pick_cost: MIN_PICK_COST,
+            distribution_time_offset: PRIZE_DISTRIBUTION_TIME_OFFSET,
+        };
+    }
+}
+
+impl PrizeConfig{
+    pub fn assert_valid(&self){
+        let tiers_sum: u128 = self.tiers.iter().map(|el| u128::from(*el)).sum();
+        assert!(self.tiers_nominal > 0, "Tiers nominal should be a positive number");
+        assert_eq!(tiers_sum, self.tiers_nominal, "Tiers should sum to the tiers nominal");
+        assert!(self.bit_range_size > 0, "Bit range size should be a positive number");
+        assert!(self.max_cardinality > 0, "Max cardinality should be a positive number");
+        // the tier of a pick is the number of unmatched masks, so there should be a tier for every mask
+        assert!(usize::from(self.max_cardinality) < MAX_TIERS, "Max cardinality should be less than {}", MAX_TIERS);
+        assert!(
+            u32::from(self.bit_range_size) * u32::from(self.max_cardinality) <= 256, 
+            "Bit range size * max cardinality should fit in 256 bits"
+        );
+        assert!(self.min_pick_cost > 0, "Min pick cost should be a positive number");
+    }
+
+    /// The number of picks for the given cardinality, saturated at u128::MAX
+    pub fn number_of_picks(&self, cardinality: u8) -> u128{
+        let bits = u32::from(self.bit_range_size) * u32::from(cardinality);
+        if bits >= u128::BITS{
+            return u128::MAX;
+        }
+
+        return 1u128 << bits;
+    }
+
+    /// The smallest cardinality with enough picks for `max_picks`, capped at `max_cardinality`
+    pub fn cardinality_for(&self, max_picks: u128) -> u8{
+        let mut cardinality:u8 = 0;
+        while cardinality < self.max_cardinality && self.number_of_picks(cardinality) < max_picks {
+            cardinality += 1;
+        }
+
+        return cardinality;
+    }
+}
+
+/// Where the unclaimed prize of a prize distribution goes after the claiming ends
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum SweepTarget{
+    NextDistribution,
+    Reserve,
+}
+
 #[derive(BorshSerialize, BorshDeserialize)]
 pub struct PrizeBuffer{
     pub buffer: GenericRingBuffer<PrizeDistribution, MAX_PRIZES_CAPACITY>,
@@ -61,9 +120,15 @@ impl Contract{
     fn number_of_prizes_for_tier(&self, tier: u8, bit_range_size: u8) -> u64{
         if tier == 0{
             return 1;
-        }else{
-            return ( 1 << (bit_range_size * tier) ) - ( 1 << (bit_range_size * (tier - 1)) );
         }
+
+        let bits = u32::from(bit_range_size) * u32::from(tier);
+        // the fraction of such a tier rounds down to zero anyway
+        if bits >= u64::BITS{
+            return u64::MAX;
+        }
+
+        return ( 1 << bits ) - ( 1 << (bits - u32::from(bit_range_size)) );
     }
 
     fn prize_tier_fraction(&self, tier_idx: u8, bit_range_size: u8, prize_tiers: &[u32]) -> u64{
@@ -74,36 +139,276 @@ impl Contract{
         return (prize_fraction as u64) / number_of_prizes_for_tier;
     }
 
+    /// Adds the prize distribution of the draw. The prize is the yield earned over the draw period
+    /// together with the unclaimed prizes rolled over to it. `position_result` is the pool balance in every yield source,
+    /// the balance above the allocation is the yield that pays the prizes
     #[private]
-    pub fn on_get_draw_and_add_prize_distribution(&mut self, prize_awards: Balance, #[callback_result] call_result: Result<Draw, PromiseError>) {
+    pub fn on_get_draw_and_add_prize_distribution(
+        &mut self, 
+        redeems_started: u64,
+        #[callback_result] call_result: Result<Draw, PromiseError>, 
+        #[callback_result] position_result: Result<Vec<Balance>, PromiseError>
+    ) {
         if call_result.is_err(){
-            log!("Error when getting draw");
+            panic!("Error when getting draw");
+        }
+
+        if position_result.is_err(){
+            panic!("Error when getting the balance from the yield sources");
         }
 
         let draw = call_result.unwrap();
-        let mut cardinality:u8 = 0;
-        let tickets_supply = self.tickets.average_total_supply_between_timestamps(draw.started_at, draw.completed_at);
-        let max_picks = tickets_supply / MIN_PICK_COST;
-        let bit_range_sized_two = 2u8.pow(BIT_RANGE_SIZE.into());
-        while u128::from(bit_range_sized_two.pow(cardinality.into())) < max_picks {
-            cardinality += 1;
+        // another call could have added it while the draw and the position were fetched
+        if self.get_prize_distribution(draw.draw_id) != PrizeDistribution::default(){
+            return;
         }
 
-        let number_of_picks: u64 = bit_range_sized_two.pow(cardinality.into()).into();
+        // the redeemed tokens are taken from the allocations before they leave the yield source
+        let balances = position_result.unwrap();
+        assert!(
+            self.yield_sources.is_settled_since(redeems_started) && self.migration.is_none() &&
+                balances.len() == self.yield_sources.sources.len(),
+            "The yield sources changed while the position was read"
+        );
+        self.yield_sources.set_unpaid_yields(&balances);
+        let reward = self.get_reward_for_position(balances.iter().sum::<Balance>() + self.yield_sources.held());
+        let prize_awards = self.accounting.yield_for_period(reward, draw.started_at, draw.completed_at, env::block_timestamp_ms());
+        // the config at the time of the creation is copied, later updates don't change this distribution
+        let config = self.prize_config;
+        let tickets_supply = self.tickets.average_total_supply_between_timestamps(draw.started_at, draw.completed_at);
+        let max_picks = tickets_supply / config.min_pick_cost;
+        let cardinality = config.cardinality_for(max_picks);
+        let prize = prize_awards + self.accounting.rollover;
+        self.accounting.rollover = 0;
+        self.accounting.on_harvest(draw.draw_id, prize_awards, draw.completed_at);
+
+        let number_of_picks = u64::try_from(config.number_of_picks(cardinality)).unwrap_or(u64::MAX);
         let prize_distribution = PrizeDistribution {
             number_of_picks: number_of_picks, 
             draw_id: draw.draw_id,
             cardinality: cardinality,
-            bit_range_size: BIT_RANGE_SIZE,
-            tiers: TIERS,
+            bit_range_size: config.bit_range_size,
+            tiers: config.tiers,
+            tiers_nominal: config.tiers_nominal,
             max_picks: max_picks,
-            prize: prize_awards,
-            start_time: draw.completed_at + PRIZE_DISTRIBUTION_TIME_OFFSET,
-            end_time: draw.completed_at + 2 * PRIZE_DISTRIBUTION_TIME_OFFSET,
+            prize: prize,
+            claimed: 0,
+            swept: false,
+            start_time: draw.completed_at + config.distribution_time_offset,
+            end_time: draw.completed_at + 2 * config.distribution_time_offset,
+            draw_started_at: draw.started_at,
             winning_number: draw.winning_random_number,
         };
 
         self.prizes.buffer.add(&prize_distribution);
+        self.update_twab_prune_before();
+    }
+
+    /// The twabs are needed from the start of the oldest draw whose prize can still be claimed.
+    /// If none can be claimed, newer draws started after the newest prize distribution
+    fn compute_twab_prune_before(&self, current_time: u64) -> Option<u64>{
+        let prize_distributions = self.prizes.buffer.arr
+            .iter()
+            .filter(|el| **el != PrizeDistribution::default());
+
+        let mut oldest_claimable: Option<u64> = None;
+        let mut newest: Option<u64> = None;
+        for prize_distribution in prize_distributions{
+            let started_at = prize_distribution.draw_started_at;
+            if prize_distribution.end_time >= current_time{
+                oldest_claimable = Some(oldest_claimable.map_or(started_at, |el| el.min(started_at)));
+            }
+            newest = Some(newest.map_or(started_at, |el| el.max(started_at)));
+        }
+
+        return oldest_claimable.or(newest);
+    }
+
+    pub (crate) fn update_twab_prune_before(&mut self){
+        if let Some(prune_before) = self.compute_twab_prune_before(env::block_timestamp_ms()){
+            let mut settings = self.tickets.settings;
+            settings.prune_before = settings.prune_before.max(prune_before);
+            self.set_twab_settings(settings);
+        }
+    }
+
+    /// The prizes that are still owed to the winners or kept aside, they are not part of the yield
+    pub (crate) fn get_committed_prizes(&self) -> Balance{
+        let outstanding: Balance = self.prizes.buffer.arr
+            .iter()
+            .filter(|el| **el != PrizeDistribution::default() && !el.swept)
+            .map(|el| el.prize.saturating_sub(el.claimed))
+            .sum();
+
+        return outstanding + self.accounting.rollover + self.accounting.reserve;
+    }
+
+    fn get_prize_distribution_mut(&mut self, draw_id: DrawId) -> Option<&mut PrizeDistribution>{
+        return self.prizes.buffer.arr
+            .iter_mut()
+            .find(|el| el.draw_id == draw_id && **el != PrizeDistribution::default());
+    }
+
+    fn assert_claim_window(&self, prize_distribution: &PrizeDistribution){
+        let current_time = env::block_timestamp_ms();
+        assert!(
+            current_time >= prize_distribution.start_time, 
+            "The claiming for draw {} starts at {}", prize_distribution.draw_id, prize_distribution.start_time
+        );
+        assert!(
+            current_time <= prize_distribution.end_time, 
+            "The claiming for draw {} ended at {}", prize_distribution.draw_id, prize_distribution.end_time
+        );
+    }
+
+    /// Reverts the claimed amount of a failed payout.
+    /// If the prize distribution was swept meanwhile, the amount goes to the reserve
+    pub (crate) fn on_claim_reverted(&mut self, draw_id: DrawId, amount: Balance){
+        self.accounting.on_prize_payment_reverted(amount);
+        let mut reserved = false;
+        match self.get_prize_distribution_mut(draw_id){
+            Some(prize_distribution) if !prize_distribution.swept => 
+                prize_distribution.claimed -= amount,
+            _ => reserved = true,
+        };
+
+        if reserved{
+            self.accounting.reserve += amount;
+            events::unclaimed_prize_reserved(draw_id, amount);
+        }
+    }
+
+    fn get_pick_prize(&self, account_id: &AccountId, prize_distribution: &PrizeDistribution, pick: NumPicks) -> (u8, Balance){
+        let user_winning_number = utils::utils::get_user_winning_number(account_id, pick);
+        let masks = self.create_masks(prize_distribution.bit_range_size, prize_distribution.cardinality);
+        // get tier match
+        let tier_match = self.get_tier_match(&masks, &user_winning_number, &prize_distribution.winning_number);
+        // get prize tier fraction
+        let prize_tier_fraction = self.prize_tier_fraction(tier_match, prize_distribution.bit_range_size, &prize_distribution.tiers);
+        let prize_to_take = u128::from(prize_tier_fraction) * prize_distribution.prize / prize_distribution.tiers_nominal;
+
+        return (tier_match, prize_to_take);
+    }
+
+    /// Validates the picks, records the winning ones as claimed and pays the total prize.
+    /// If the payout fails, the recorded picks are removed
+    fn internal_claim(&mut self, account_id: &AccountId, claims: Vec<(U128, Vec<U128>)>) -> Vec<PickPrizeView>{
+        self.assert_not_paused(Operation::Claim);
+        self.assert_not_paused(Operation::YieldTransfer);
+
+        let mut prizes: Vec<PickPrizeView> = Vec::new();
+        let mut claimed: Vec<(DrawId, NumPicks, Balance)> = Vec::new();
+        let mut total_prize: Balance = 0;
+
+        for (draw_id, picks) in claims.iter(){
+            let prize_distribution = self.get_prize_distribution(draw_id.0);
+            assert!(prize_distribution != PrizeDistribution::default(), "There is no prize distribution for draw {}", draw_id.0);
+            self.assert_claim_window(&prize_distribution);
+            let picks_for_draw = self.acc_picks.get_picks_for_draw(account_id, &draw_id.0);
+            
+            if picks_for_draw == NumPicks::default(){
+                panic!("There are no generated picks for draw {} for client", draw_id.0);
+            }
+
+            for pick in picks.iter(){
+                if pick.0 >= picks_for_draw {
+                    panic!("Invalid pick {}", pick.0);
+                }
+
+                if self.claims.is_claimed(account_id, &draw_id.0, &pick.0){
+                    panic!("The pick {} is already claimed for draw {}", pick.0, draw_id.0);
+                }
+
+                let (tier, pick_prize) = self.get_pick_prize(account_id, &prize_distribution, pick.0);
+                // the winners of a draw can't take more than its prize
+                let claimed_so_far = self.get_prize_distribution(draw_id.0).claimed;
+                let prize_to_take = std::cmp::min(pick_prize, prize_distribution.prize.saturating_sub(claimed_so_far));
+                if prize_to_take > 0{
+                    // the pick is recorded before the payout, so a repeated pick in the same batch fails here
+                    self.claims.add_claim(account_id, &draw_id.0, pick.0, prize_to_take);
+                    claimed.push((draw_id.0, pick.0, prize_to_take));
+                    total_prize += prize_to_take;
+                    self.get_prize_distribution_mut(draw_id.0).unwrap().claimed += prize_to_take;
+                }
+
+                prizes.push(PickPrizeView { draw_id: *draw_id, pick: *pick, tier: tier, amount: U128(prize_to_take) });
+            }
+        }
+
+        assert!(total_prize > 0, "There is no prize for the claimed picks");
+        self.accounting.on_prize_paid(total_prize);
+
+        log!("Prize to claim is {} {}", total_prize, self.deposited_token_id);
+        // the idle tokens pay the prize without a call to the yield source
+        if self.yield_sources.take_idle(total_prize){
+            self.transfer_prize(account_id.clone(), claimed);
+
+            return prizes;
+        }
+
+        // every yield source pays its share of the prize from its yield, the idle tokens pay what the yield doesn't cover
+        let redeems = self.yield_sources.prize_redeems(total_prize);
+        let to_redeem: Balance = redeems.iter().map(|(_, amount)| amount).sum();
+        assert!(to_redeem + self.yield_sources.idle >= total_prize, "The yield of the pool doesn't cover the prize");
+
+        self.yield_sources.start_redeem();
+        redeems
+            .iter()
+            .map(|(source, amount)| self.get_yield_source(source).redeem(&self.deposited_token_id, *amount))
+            .reduce(|acc, el| acc.and(el))
+            .expect("There is no yield source to redeem the prize from")
+            .then(this_contract::on_prize_redeemed(account_id.clone(), claimed, redeems, env::current_account_id(), 0, gas::GAS_FOR_AFTER_WITHDRAW_FROM_DEFI));
+
+        return prizes;
+    }
+
+    /// Moves the unclaimed prize of a prize distribution whose claiming ended
+    /// to the next prize distribution or to the reserve
+    pub fn sweep_unclaimed_prize(&mut self, draw_id: U128, target: SweepTarget) -> U128{
+        self.assert_role(Role::Keeper);
+        let prize_distribution = self.get_prize_distribution_mut(draw_id.0)
+            .unwrap_or_else(|| panic!("There is no prize distribution for draw {}", draw_id.0));
+        assert!(env::block_timestamp_ms() > prize_distribution.end_time, "The claiming for draw {} has not ended", draw_id.0);
+        assert!(!prize_distribution.swept, "The prize for draw {} is already swept", draw_id.0);
+
+        prize_distribution.swept = true;
+        let unclaimed = prize_distribution.prize.saturating_sub(prize_distribution.claimed);
+
+        match target{
+            SweepTarget::NextDistribution => {
+                self.accounting.rollover += unclaimed;
+                events::unclaimed_prize_rolled_over(draw_id.0, unclaimed);
+            },
+            SweepTarget::Reserve => {
+                self.accounting.reserve += unclaimed;
+                events::unclaimed_prize_reserved(draw_id.0, unclaimed);
+            },
+        }
+
+        return U128(unclaimed);
+    }
+
+    pub fn get_prize_rollover(&self) -> U128{
+        return U128(self.accounting.rollover);
+    }
+
+    pub fn get_prize_reserve(&self) -> U128{
+        return U128(self.accounting.reserve);
+    }
+
+    pub fn get_prize_config(&self) -> PrizeConfig{
+        return self.prize_config;
+    }
+
+    /// Replaces the prize config. Only the prize distributions added after the update use it
+    #[payable]
+    pub fn set_prize_config(&mut self, config: PrizeConfig){
+        assert_one_yocto();
+        self.assert_role(Role::Admin);
+        config.assert_valid();
+
+        events::prize_config_updated(&self.prize_config, &config, &env::predecessor_account_id());
+        self.prize_config = config;
     }
 }
 
@@ -119,54 +424,54 @@ impl PrizeDistributionActor for Contract{
         return PrizeDistribution::default();
     }
 
-    fn add_prize_distribution(&mut self, draw_id: DrawId, prize_awards: Balance) {
+    fn add_prize_distribution(&mut self, draw_id: DrawId) {
+        self.assert_role(Role::Keeper);
         if self.get_prize_distribution(draw_id) != PrizeDistribution::default(){
             return;
         }
+        assert!(self.yield_sources.pending_redeems == 0, "A redeem from the yield sources is pending");
+        assert!(self.migration.is_none(), "A yield source migration is in progress");
+
         let draw_promise = ext_draw::get_draw(draw_id, self.draw_contract.clone(), 0, gas::GET_DRAW);
-        draw_promise.then(
-            this_contract::on_get_draw_and_add_prize_distribution(prize_awards, env::current_account_id(), 0, gas::GET_DRAW)
+        let position_promise = self.get_pool_position();
+        draw_promise.and(position_promise).then(
+            this_contract::on_get_draw_and_add_prize_distribution(self.yield_sources.redeems_started, env::current_account_id(), 0, gas::GET_DRAW)
         );
     }
 
     #[payable]
     fn claim(&mut self, draw_id: U128, pick: U128) -> u128{
         assert_one_yocto();
-        
-        let prize_distribution = self.get_prize_distribution(draw_id.0);
         let caller = env::signer_account_id();
-        let picks_for_draw = self.acc_picks.get_picks_for_draw(&caller, &draw_id.0);
-        
-        if picks_for_draw == NumPicks::default(){
-            panic!("There are no generated picks for this draw for client");
-        }
+        let prizes = self.internal_claim(&caller, vec![(draw_id, vec![pick])]);
 
-        if pick.0 >= picks_for_draw {
-            panic!("Invalid pick");
-        }
+        return prizes[0].amount.0;
+    }
 
-        let user_winning_number = utils::utils::get_user_winning_number(&caller, pick.0);
-        let masks = self.create_masks(prize_distribution.bit_range_size, prize_distribution.cardinality);
-        // get tier match
-        let tier_match = self.get_tier_match(&masks, &user_winning_number, &prize_distribution.winning_number);
-        // get prize tier fraction
-        let prize_tier_fraction = self.prize_tier_fraction(tier_match, prize_distribution.bit_range_size, &prize_distribution.tiers);
-        let prize_to_take = u128::from(prize_tier_fraction) * prize_distribution.prize / TIERS_NOMINAL;
+    /// Claims all given picks for every draw with at most one redeem from every yield source.
+    /// Returns the tier and the prize of every pick, the picks without a prize are not recorded
+    #[payable]
+    fn claim_many(&mut self, claims: Vec<(U128, Vec<U128>)>) -> Vec<PickPrizeView>{
+        assert_one_yocto();
+        let caller = env::signer_account_id();
 
-        log!("Prize to claim is {} {}", prize_to_take, self.deposited_token_id);
-        self.get_yield_source().claim(&caller, &self.deposited_token_id, prize_to_take);
-        
-        return prize_to_take;
+        return self.internal_claim(&caller, claims);
     }
 }
 
 #[cfg(test)]
 mod tests{
     use crate::*;
-    use crate::test_utils::{get_contract};
+    use crate::test_utils::{get_contract, mmmm, sec, burrow, set_caller, returned};
+    use crate::interfaces::prize_distribution::{PrizeDistribution, PrizeDistributionActor};
+    use common::generic_ring_buffer::RingBuffer;
     use common::types::U256;
 
-    use super::TIERS;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{testing_env, PromiseResult, VMConfig, RuntimeFeesConfig};
+    use crate::interfaces::prize_distribution::PrizeConfig;
+
+    use super::{TIERS, SweepTarget};
 
     #[test]
     fn test_masks(){
@@ -227,6 +532,27 @@ mod tests{
         assert_eq!(prizes_number, 15);
         let prizes_number = contract.number_of_prizes_for_tier(1, 1);
         assert_eq!(prizes_number, 1);
+        let prizes_number = contract.number_of_prizes_for_tier(8, 8);
+        assert_eq!(prizes_number, u64::MAX);
+    }
+
+    #[test]
+    fn test_twab_prune_before(){
+        let mut contract = get_contract();
+        assert_eq!(contract.compute_twab_prune_before(0), None);
+
+        for (draw_id, draw_started_at) in [(1, 100), (2, 200), (3, 300)]{
+            let mut prize_distribution = PrizeDistribution::default();
+            prize_distribution.draw_id = draw_id;
+            prize_distribution.draw_started_at = draw_started_at;
+            prize_distribution.end_time = draw_started_at + 1000;
+            contract.prizes.buffer.add(&prize_distribution);
+        }
+
+        assert_eq!(contract.compute_twab_prune_before(1000), Some(100));
+        assert_eq!(contract.compute_twab_prune_before(1150), Some(200));
+        // nothing can be claimed, keep the history from the newest draw
+        assert_eq!(contract.compute_twab_prune_before(2000), Some(300));
     }
 
     #[test]
@@ -248,4 +574,389 @@ mod tests{
         let prize_fraction = contract.prize_tier_fraction(2, 2, &TIERS);
         assert_eq!(prize_fraction, 1);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_cardinality_for(){
+        let config = PrizeConfig::default();
+        assert_eq!(config.cardinality_for(0), 0);
+        assert_eq!(config.cardinality_for(1), 0);
+        assert_eq!(config.cardinality_for(2), 1);
+        assert_eq!(config.cardinality_for(5), 3);
+        // capped so there is a tier for every mask
+        assert_eq!(config.cardinality_for(u128::MAX), config.max_cardinality);
+
+        let mut config = PrizeConfig::default();
+        config.bit_range_size = 128;
+        config.max_cardinality = 2;
+        assert_eq!(config.number_of_picks(1), u128::MAX);
+        assert_eq!(config.cardinality_for(u128::MAX), 1);
+    }
+
+    #[test]
+    fn test_set_prize_config(){
+        let mut contract = get_contract();
+        set_caller(mmmm());
+
+        let mut config = PrizeConfig::default();
+        config.tiers = [50,50,0,0,0,0,0,0,0,0,0,0,0,0,0,0];
+        config.bit_range_size = 4;
+        contract.set_prize_config(config);
+        assert_eq!(contract.get_prize_config(), config);
+    }
+
+    #[test]
+    #[should_panic(expected = "Tiers should sum to the tiers nominal")]
+    fn test_set_prize_config_invalid_tiers(){
+        let mut contract = get_contract();
+        set_caller(mmmm());
+
+        let mut config = PrizeConfig::default();
+        config.tiers_nominal = 1000;
+        contract.set_prize_config(config);
+    }
+
+    #[test]
+    #[should_panic(expected = "Bit range size * max cardinality should fit in 256 bits")]
+    fn test_set_prize_config_too_many_bits(){
+        let mut contract = get_contract();
+        set_caller(mmmm());
+
+        let mut config = PrizeConfig::default();
+        config.bit_range_size = 32;
+        config.max_cardinality = 9;
+        contract.set_prize_config(config);
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller doesn't have the Admin role")]
+    fn test_set_prize_config_not_admin(){
+        let mut contract = get_contract();
+        set_caller(sec());
+
+        contract.set_prize_config(PrizeConfig::default());
+    }
+
+    fn add_winning_draw(contract: &mut Contract, account_id: &AccountId, draw_id: DrawId){
+        let mut prize_distribution = PrizeDistribution::default();
+        prize_distribution.draw_id = draw_id;
+        prize_distribution.cardinality = 2;
+        prize_distribution.bit_range_size = 1;
+        prize_distribution.tiers = TIERS;
+        prize_distribution.tiers_nominal = 100;
+        prize_distribution.prize = 1000;
+        prize_distribution.start_time = 100;
+        prize_distribution.end_time = 200;
+        // the first pick of the account matches all masks
+        prize_distribution.winning_number = utils::utils::get_user_winning_number(account_id, 0);
+        contract.prizes.buffer.add(&prize_distribution);
+        contract.acc_picks.add_picks_for_draw(account_id, &draw_id, 4);
+    }
+
+    #[test]
+    fn test_claim_many(){
+        let mut contract = get_contract();
+        testing_env!(VMContextBuilder::new()
+            .signer_account_id(sec())
+            .attached_deposit(1)
+            .block_timestamp(ms_to_ns(150))
+            .build());
+        add_winning_draw(&mut contract, &sec(), 1);
+        add_winning_draw(&mut contract, &sec(), 2);
+        contract.yield_sources.sources[0].unpaid_yield = 1000;
+
+        let prizes = contract.claim_many(vec![(U128(1), vec![U128(0), U128(1)]), (U128(2), vec![U128(0)])]);
+        assert_eq!(prizes.len(), 3);
+        assert_eq!(prizes[0].tier, 0);
+        assert_eq!(prizes[0].amount, U128(200));
+        assert_eq!(prizes[2].amount, U128(200));
+
+        assert!(contract.is_claimed(sec(), U128(1), U128(0)));
+        assert!(contract.is_claimed(sec(), U128(2), U128(0)));
+        // only the winning picks are recorded
+        assert_eq!(contract.is_claimed(sec(), U128(1), U128(1)), prizes[1].amount.0 > 0);
+    }
+
+    #[test]
+    fn test_claim_capped_at_prize_left(){
+        let mut contract = get_contract();
+        add_winning_draw(&mut contract, &sec(), 1);
+        contract.yield_sources.idle = 1000;
+        contract.get_prize_distribution_mut(1).unwrap().claimed = 950;
+
+        set_keeper_context(150);
+        assert_eq!(contract.claim(U128(1), U128(0)), 50);
+        assert_eq!(contract.get_prize_distribution(1).claimed, 1000);
+        assert_eq!(contract.get_committed_prizes(), 0);
+
+        set_keeper_context(201);
+        assert_eq!(contract.sweep_unclaimed_prize(U128(1), SweepTarget::Reserve), U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "The pick 0 is already claimed for draw 1")]
+    fn test_claim_many_repeated_pick(){
+        let mut contract = get_contract();
+        testing_env!(VMContextBuilder::new()
+            .signer_account_id(sec())
+            .attached_deposit(1)
+            .block_timestamp(ms_to_ns(150))
+            .build());
+        add_winning_draw(&mut contract, &sec(), 1);
+        contract.yield_sources.sources[0].unpaid_yield = 1000;
+
+        contract.claim(U128(1), U128(0));
+        contract.claim_many(vec![(U128(1), vec![U128(0)])]);
+    }
+
+    fn burrow_source() -> YieldSource{
+        return YieldSource::Burrow { address: burrow() };
+    }
+
+    fn ms_to_ns(ms: u64) -> u64{
+        return ms * 1_000_000;
+    }
+
+    fn set_keeper_context(block_timestamp_ms: u64){
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(mmmm())
+            .predecessor_account_id(mmmm())
+            .signer_account_id(sec())
+            .attached_deposit(1)
+            .block_timestamp(ms_to_ns(block_timestamp_ms))
+            .build());
+    }
+
+    fn set_redeem_result(block_timestamp_ms: u64, result: PromiseResult){
+        testing_env!(
+            VMContextBuilder::new()
+                .current_account_id(mmmm())
+                .predecessor_account_id(mmmm())
+                .block_timestamp(ms_to_ns(block_timestamp_ms))
+                .build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![result]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "The claiming for draw 1 ended at 200")]
+    fn test_claim_after_window(){
+        let mut contract = get_contract();
+        add_winning_draw(&mut contract, &sec(), 1);
+        set_keeper_context(201);
+
+        contract.claim(U128(1), U128(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "The claiming for draw 1 starts at 100")]
+    fn test_claim_before_window(){
+        let mut contract = get_contract();
+        add_winning_draw(&mut contract, &sec(), 1);
+        set_keeper_context(99);
+
+        contract.claim(U128(1), U128(0));
+    }
+
+    #[test]
+    fn test_claim_paid_from_idle_tokens(){
+        let mut contract = get_contract();
+        add_winning_draw(&mut contract, &sec(), 1);
+        contract.yield_sources.idle = 500;
+
+        set_keeper_context(150);
+        contract.claim(U128(1), U128(0));
+        assert_eq!(contract.get_idle_balance(), U128(300));
+
+        // a failed payout gives the tokens back to the idle buffer
+        contract.on_after_claim(sec(), vec![(1, 0, 200)], Err(PromiseError::Failed));
+        assert_eq!(contract.get_idle_balance(), U128(500));
+    }
+
+    #[test]
+    fn test_claim_transfer_failed_after_redeem(){
+        let mut contract = get_contract();
+        add_winning_draw(&mut contract, &sec(), 1);
+        contract.yield_sources.sources[0].allocated = 1000;
+        contract.yield_sources.sources[0].unpaid_yield = 1000;
+
+        set_keeper_context(150);
+        contract.claim(U128(1), U128(0));
+        set_redeem_result(150, PromiseResult::Successful(vec![]));
+        contract.on_prize_redeemed(sec(), vec![(1, 0, 200)], vec![(burrow_source(), 200)]);
+        assert!(contract.is_claimed(sec(), U128(1), U128(0)));
+
+        // the prize is already out of the yield source, so the pool holds it
+        contract.on_after_claim(sec(), vec![(1, 0, 200)], Err(PromiseError::Failed));
+        assert!(!contract.is_claimed(sec(), U128(1), U128(0)));
+        assert_eq!(contract.get_idle_balance(), U128(200));
+        // the prize is paid from the yield, the principal allocation stays the same
+        assert_eq!(contract.yield_sources.sources[0].allocated, 1000);
+    }
+
+    #[test]
+    fn test_prize_redeemed_amount(){
+        let mut contract = get_contract();
+        add_winning_draw(&mut contract, &sec(), 1);
+        add_winning_draw(&mut contract, &sec(), 2);
+        contract.yield_sources.sources[0].allocated = 1000;
+        contract.yield_sources.sources[0].unpaid_yield = 1000;
+        contract.yield_sources.idle = 5;
+
+        // the unstaked NEAR above the prize stays in the pool
+        set_keeper_context(150);
+        contract.claim(U128(1), U128(0));
+        set_redeem_result(150, returned(203));
+        contract.on_prize_redeemed(sec(), vec![(1, 0, 200)], vec![(burrow_source(), 200)]);
+        assert_eq!(contract.get_idle_balance(), U128(8));
+        assert!(contract.is_claimed(sec(), U128(1), U128(0)));
+
+        // the idle tokens don't cover the missing part of the prize, it can be claimed again
+        set_keeper_context(150);
+        contract.claim(U128(2), U128(0));
+        set_redeem_result(150, returned(190));
+        contract.on_prize_redeemed(sec(), vec![(2, 0, 200)], vec![(burrow_source(), 200)]);
+        assert!(!contract.is_claimed(sec(), U128(2), U128(0)));
+        assert_eq!(contract.get_prize_distribution(2).claimed, 0);
+        assert_eq!(contract.get_idle_balance(), U128(198));
+        assert_eq!(contract.yield_sources.sources[0].allocated, 1000);
+    }
+
+    #[test]
+    fn test_prize_split_across_yield_sources(){
+        let mut contract = get_contract();
+        add_winning_draw(&mut contract, &sec(), 1);
+        set_caller(mmmm());
+        let metapool = YieldSource::Metapool { address: AccountId::new_unchecked("metapool".to_string()) };
+        contract.add_yield_source(metapool.clone(), 100);
+        contract.yield_sources.sources[0].unpaid_yield = 300;
+        contract.yield_sources.sources[1].unpaid_yield = 100;
+
+        // the Meta Pool redeem fails, the tokens of the Burrow one stay idle
+        set_keeper_context(150);
+        contract.claim(U128(1), U128(0));
+        assert_eq!(contract.yield_sources.sources[0].unpaid_yield, 150);
+        assert_eq!(contract.yield_sources.sources[1].unpaid_yield, 50);
+        testing_env!(
+            VMContextBuilder::new().current_account_id(mmmm()).predecessor_account_id(mmmm()).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![]), PromiseResult::Failed]
+        );
+        contract.on_prize_redeemed(sec(), vec![(1, 0, 200)], vec![(burrow_source(), 150), (metapool, 50)]);
+        assert!(!contract.is_claimed(sec(), U128(1), U128(0)));
+        assert_eq!(contract.get_idle_balance(), U128(150));
+        assert_eq!(contract.yield_sources.sources[1].unpaid_yield, 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "The yield of the pool doesn't cover the prize")]
+    fn test_claim_without_yield(){
+        let mut contract = get_contract();
+        add_winning_draw(&mut contract, &sec(), 1);
+        contract.yield_sources.sources[0].unpaid_yield = 100;
+        contract.yield_sources.idle = 50;
+
+        set_keeper_context(150);
+        contract.claim(U128(1), U128(0));
+    }
+
+    #[test]
+    fn test_sweep_unclaimed_prize(){
+        let mut contract = get_contract();
+        add_winning_draw(&mut contract, &sec(), 1);
+        add_winning_draw(&mut contract, &sec(), 2);
+        contract.yield_sources.sources[0].unpaid_yield = 1000;
+
+        set_keeper_context(150);
+        contract.claim(U128(1), U128(0));
+        set_redeem_result(150, PromiseResult::Successful(vec![]));
+        contract.on_prize_redeemed(sec(), vec![(1, 0, 200)], vec![(burrow_source(), 200)]);
+        // a failed payout gives back the claimed amount
+        set_keeper_context(150);
+        contract.claim(U128(2), U128(0));
+        set_redeem_result(150, PromiseResult::Failed);
+        contract.on_prize_redeemed(sec(), vec![(2, 0, 200)], vec![(burrow_source(), 200)]);
+        assert_eq!(contract.get_prize_distribution(2).claimed, 0);
+
+        set_keeper_context(201);
+        assert_eq!(contract.sweep_unclaimed_prize(U128(1), SweepTarget::Reserve), U128(800));
+        assert_eq!(contract.sweep_unclaimed_prize(U128(2), SweepTarget::NextDistribution), U128(1000));
+        assert_eq!(contract.get_prize_reserve(), U128(800));
+        assert_eq!(contract.get_prize_rollover(), U128(1000));
+
+        // the position holds the reserve, the rollover and 50 of yield
+        contract.tickets.increase_total_supply(100, 0);
+        contract.on_get_draw_and_add_prize_distribution(2, Ok(Draw { draw_id: 3, winning_random_number: U256::one(), started_at: 0, completed_at: 201 }), Ok(vec![1850]));
+        assert_eq!(contract.get_prize_distribution(3).prize, 1050);
+        assert_eq!(contract.get_prize_rollover(), U128(0));
+    }
+
+    #[test]
+    fn test_prize_is_yield_over_draw_period(){
+        let mut contract = get_contract();
+        contract.accounting.on_deposit(1000);
+        contract.tickets.increase_total_supply(1000, 0);
+        contract.yield_sources.idle = 100;
+
+        // 300 of yield was earned since 0, the draw lasted a third of that time
+        contract.yield_sources.sources[0].allocated = 900;
+        set_keeper_context(300);
+        contract.on_get_draw_and_add_prize_distribution(0, Ok(Draw { draw_id: 1, winning_random_number: U256::one(), started_at: 100, completed_at: 200 }), Ok(vec![1200]));
+        assert_eq!(contract.get_prize_distribution(1).prize, 100);
+        // the balance above the allocation pays the prizes
+        assert_eq!(contract.yield_sources.sources[0].unpaid_yield, 300);
+        assert_eq!(contract.get_yield_harvested(U128(1)), U128(100));
+        assert_eq!(contract.accounting.last_harvested_at, 200);
+
+        // the rest of the yield was earned after the draw, the next draw gets it
+        set_keeper_context(400);
+        contract.on_get_draw_and_add_prize_distribution(0, Ok(Draw { draw_id: 2, winning_random_number: U256::one(), started_at: 200, completed_at: 400 }), Ok(vec![1200]));
+        assert_eq!(contract.get_prize_distribution(2).prize, 200);
+    }
+
+    #[test]
+    #[should_panic(expected = "The yield sources changed while the position was read")]
+    fn test_prize_distribution_after_redeem(){
+        let mut contract = get_contract();
+        contract.yield_sources.start_redeem();
+        contract.yield_sources.end_redeem();
+
+        set_keeper_context(300);
+        contract.on_get_draw_and_add_prize_distribution(0, Ok(Draw { draw_id: 1, winning_random_number: U256::one(), started_at: 100, completed_at: 200 }), Ok(vec![1200]));
+    }
+
+    #[test]
+    #[should_panic(expected = "A redeem from the yield sources is pending")]
+    fn test_add_prize_distribution_with_pending_redeem(){
+        let mut contract = get_contract();
+        contract.yield_sources.start_redeem();
+
+        set_keeper_context(300);
+        contract.add_prize_distribution(1);
+    }
+
+    #[test]
+    #[should_panic(expected = "The claiming for draw 1 has not ended")]
+    fn test_sweep_before_end(){
+        let mut contract = get_contract();
+        add_winning_draw(&mut contract, &sec(), 1);
+        set_keeper_context(200);
+
+        contract.sweep_unclaimed_prize(U128(1), SweepTarget::Reserve);
+    }
+
+    #[test]
+    #[should_panic(expected = "The prize for draw 1 is already swept")]
+    fn test_sweep_twice(){
+        let mut contract = get_contract();
+        add_winning_draw(&mut contract, &sec(), 1);
+        set_keeper_context(201);
+
+        contract.sweep_unclaimed_prize(U128(1), SweepTarget::Reserve);
+        contract.sweep_unclaimed_prize(U128(1), SweepTarget::NextDistribution);
+    }
+}
@@ -1,6 +1,9 @@
-use near_sdk::AccountId;
+use near_sdk::json_types::U128;
+use near_sdk::test_utils::VMContextBuilder;
+use near_sdk::{testing_env, AccountId, Balance, PromiseResult, VMConfig, RuntimeFeesConfig};
+pub use common::test_utils::set_caller;
 
-use crate::{Contract};
+use crate::{Contract, TokenAmountsView};
 
 pub fn mmmm() -> AccountId{
     AccountId::new_unchecked("mmmm".to_string())
@@ -14,11 +17,47 @@ pub fn burrow() -> AccountId{
     AccountId::new_unchecked("burrow".to_string())
 }
 
+pub fn usdc() -> AccountId{
+    AccountId::new_unchecked("usdc".to_string())
+}
+
 pub fn get_contract() -> Contract{
     return Contract::new_default_meta(
         mmmm(), 
-        AccountId::new_unchecked("usdc".to_string()), 
+        usdc(), 
         sec(),
         burrow(), 
     );
 }
+
+/// The pool calls itself, so the private callbacks can be called too
+pub fn set_context(predecessor: AccountId){
+    testing_env!(VMContextBuilder::new()
+        .current_account_id(mmmm())
+        .predecessor_account_id(predecessor)
+        .attached_deposit(1)
+        .build());
+}
+
+/// Runs the next callback of the pool with the results of the promises it waited for
+pub fn set_promise_results(results: Vec<PromiseResult>){
+    testing_env!(
+        VMContextBuilder::new()
+            .current_account_id(mmmm())
+            .predecessor_account_id(mmmm())
+            .build(),
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        Default::default(),
+        results
+    );
+}
+
+/// The result of a promise that returned `amount`
+pub fn returned(amount: Balance) -> PromiseResult{
+    return PromiseResult::Successful(near_sdk::serde_json::to_vec(&U128(amount)).unwrap());
+}
+
+pub fn position(token: AccountId, shares: Balance, rewards: Balance) -> TokenAmountsView{
+    return TokenAmountsView { token: token, shares: U128(shares), rewards: U128(rewards) };
+}
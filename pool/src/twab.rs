@@ -1,43 +1,161 @@
 use crate::*;
 use interfaces::pool::ITwab;
-use near_sdk::collections::{UnorderedMap, Vector};
+use near_sdk::collections::{LookupMap, UnorderedMap};
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::IntoStorageKey;
 use utils::storage_keys::StorageKeys;
+use common::types::U256;
+
+pub const DEFAULT_MAX_TWAB_CARDINALITY: u64 = 512;
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TwabView{
+    /// U256 as a decimal string
+    pub amount: String,
+    pub timestamp: u64,
+}
 
 #[derive(Clone, Debug, Copy, Default, BorshDeserialize, BorshSerialize)]
 pub struct Twab{
-    pub amount: Balance,
+    /// Cumulative balance multiplied by the time it was held.
+    /// It doesn't fit in u128 for tokens with 24 decimals
+    pub amount: U256,
     pub timestamp: u64,
 }
 
-#[derive(BorshDeserialize, BorshSerialize)]
-pub struct AccountBalance{
-    pub balance: Balance,
-    pub twabs: Vector<Twab>,
+/// Limits the history of every account, so the storage doesn't grow forever
+#[derive(Clone, Copy, BorshDeserialize, BorshSerialize)]
+pub struct TwabSettings{
+    /// Above this number of twabs of one account the oldest ones are dropped, but only
+    /// if they are older than `prune_before`. The history of the open draws is always kept
+    pub max_cardinality: u64,
+    /// Twabs older than this timestamp are not needed for claiming prizes anymore.
+    /// The newest twab before or at it is always kept, so averages from that time on are correct
+    pub prune_before: u64,
 }
 
-impl Default for AccountBalance{
+impl Default for TwabSettings{
     fn default() -> Self {
-        AccountBalance { 
-            balance: Balance::default(), 
-            twabs: Vector::new(StorageKeys::AccountBalance) 
+        TwabSettings { max_cardinality: DEFAULT_MAX_TWAB_CARDINALITY, prune_before: 0 }
+    }
+}
+
+/// Ring buffer of twabs sorted by timestamp, addressed by sequence numbers.
+/// Adding a twab to a full buffer removes the oldest ones that are not needed anymore
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct TwabBuffer{
+    /// sequence number of the oldest twab
+    first: u64,
+    /// sequence number of the next twab
+    next: u64,
+    twabs: LookupMap<u64, Twab>,
+}
+
+impl TwabBuffer{
+    pub fn new<S: IntoStorageKey>(prefix: S) -> Self{
+        TwabBuffer { first: 0, next: 0, twabs: LookupMap::new(prefix) }
+    }
+
+    /// The buffer of the `len` twabs that are already stored under `prefix`
+    pub fn from_stored<S: IntoStorageKey>(prefix: S, len: u64) -> Self{
+        TwabBuffer { first: 0, next: len, twabs: LookupMap::new(prefix) }
+    }
+
+    pub fn len(&self) -> u64{
+        return self.next - self.first;
+    }
+
+    pub fn is_empty(&self) -> bool{
+        return self.len() == 0;
+    }
+
+    /// `idx` is counted from the oldest twab that is still kept
+    pub fn get(&self, idx: u64) -> Option<Twab>{
+        if idx >= self.len(){
+            return None;
+        }
+
+        return self.twabs.get(&(self.first + idx));
+    }
+
+    pub fn last(&self) -> Option<Twab>{
+        if self.is_empty(){
+            return None;
+        }
+
+        return self.get(self.len() - 1);
+    }
+
+    /// Twabs were dropped, so the balances before the oldest kept twab are unknown
+    pub fn has_removed_twabs(&self) -> bool{
+        return self.first > 0;
+    }
+
+    /// Above `max_cardinality` twabs the oldest ones are removed, 
+    /// but only the ones that are not needed to calculate the balances after `keep_after`
+    pub fn push(&mut self, twab: &Twab, max_cardinality: u64, keep_after: u64){
+        self.twabs.insert(&self.next, twab);
+        self.next += 1;
+
+        while self.len() > max_cardinality && self.get(1).unwrap_or_default().timestamp <= keep_after{
+            self.remove_oldest();
+        }
+    }
+
+    /// Removes the twabs that are not needed to calculate the balances after `timestamp`
+    pub fn prune_before(&mut self, timestamp: u64){
+        while self.len() > 1 && self.get(1).unwrap_or_default().timestamp <= timestamp{
+            self.remove_oldest();
         }
     }
+
+    fn remove_oldest(&mut self){
+        self.twabs.remove(&self.first);
+        self.first += 1;
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct AccountBalance{
+    pub balance: Balance,
+    pub twabs: TwabBuffer,
 }
 
 impl AccountBalance{
-    fn get_twab_before_or_at(&self, start_time: u64) -> Twab{
-        let mut before_idx = 0;
-        for idx in 0..self.twabs.len(){
-            let current_twab = self.twabs.get(idx).unwrap_or_default();
+    /// Every account has to store its twabs under its own `prefix`
+    pub fn new<S: IntoStorageKey>(prefix: S) -> Self{
+        AccountBalance { balance: Balance::default(), twabs: TwabBuffer::new(prefix) }
+    }
 
-            if current_twab.timestamp <= start_time {
-                before_idx = idx;
+    /// Writes the whole history under `prefix`, it overwrites the twabs stored there
+    pub fn from_history<S: IntoStorageKey>(prefix: S, balance: Balance, twabs: &[Twab]) -> Self{
+        let mut result = AccountBalance::new(prefix);
+        for twab in twabs.iter(){
+            result.twabs.push(twab, u64::MAX, 0);
+        }
+        result.balance = balance;
+
+        return result;
+    }
+
+    /// Binary search for the newest twab with timestamp before or at `start_time`.
+    /// The twabs are sorted by timestamp, so only O(log n) of them are read from storage
+    fn get_twab_before_or_at(&self, start_time: u64) -> Twab{
+        let mut low = 0;
+        let mut high = self.twabs.len();
+
+        // after the loop `low` is the number of twabs with timestamp <= start_time
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.twabs.get(mid).unwrap_or_default().timestamp <= start_time {
+                low = mid + 1;
             } else {
-                break;
+                high = mid;
             }
         }
 
+        let before_idx = if low == 0 { 0 } else { low - 1 };
         return self.twabs.get(before_idx).unwrap_or_default();
     }
 
@@ -52,7 +170,8 @@ impl AccountBalance{
         }
 
         if oldest_twab.timestamp > target_timestamp {
-            return Twab { amount: 0, timestamp: target_timestamp };
+            self.assert_history_kept(target_timestamp);
+            return Twab { amount: U256::zero(), timestamp: target_timestamp };
         }
 
         if newest_twab.timestamp < target_timestamp {
@@ -78,8 +197,8 @@ impl AccountBalance{
             return after_or_at;
         }
 
-        let held_balance = (after_or_at.amount - before_or_at.amount) / 
-            ((after_or_at.timestamp - before_or_at.timestamp) as u128);
+        let held_balance = ((after_or_at.amount - before_or_at.amount) / 
+            U256::from(after_or_at.timestamp - before_or_at.timestamp)).as_u128();
 
         let mut result = Twab::default();
         result.timestamp = target_timestamp;
@@ -92,58 +211,100 @@ impl AccountBalance{
         return result;
     }
 
-    fn get_twab_after_or_at(&self, end_time: u64) -> Twab{
-        let mut after_idx = 0;
-        for idx in (0..self.twabs.len()).rev(){
-            let current_twab = self.twabs.get(idx).unwrap_or_default();
+    /// The balance before the first twab is zero. When older twabs were dropped 
+    /// it is unknown, so nothing can be calculated for the time before the oldest kept twab
+    fn assert_history_kept(&self, target_timestamp: u64){
+        assert!(!self.twabs.has_removed_twabs(), "The twab history before {} was removed", target_timestamp);
+    }
 
-            if current_twab.timestamp >= end_time {
-                after_idx = idx;
+    /// Binary search for the oldest twab with timestamp after or at `end_time`
+    fn get_twab_after_or_at(&self, end_time: u64) -> Twab{
+        let mut low = 0;
+        let mut high = self.twabs.len();
+
+        // after the loop `low` is the number of twabs with timestamp < end_time
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if self.twabs.get(mid).unwrap_or_default().timestamp < end_time {
+                low = mid + 1;
             } else {
-                break;
+                high = mid;
             }
         }
 
+        let after_idx = if low == self.twabs.len() { 0 } else { low };
         return self.twabs.get(after_idx).unwrap_or_default();
     }
 
-    fn generate_twab(&mut self, current_time: u64){
-        let last_element_option = self.twabs.pop();
+    fn generate_twab(&mut self, current_time: u64, settings: &TwabSettings){
+        let last_element_option = self.twabs.last();
 
         // If there are no elements in the collection
         // just add an element
         if last_element_option.is_none() {
-            let twab_amount = self.compute_twab_balance(0, 0, current_time, 0);
-            self.twabs.push(&Twab{timestamp: current_time, amount: twab_amount});      
+            let twab_amount = self.compute_twab_balance(U256::zero(), 0, current_time, 0);
+            self.twabs.push(&Twab{timestamp: current_time, amount: twab_amount}, settings.max_cardinality, settings.prune_before);
         } else {
             let last_element = last_element_option.unwrap();
 
-            // If this balance increase is for the same timestamp increase the balance
+            // If this balance increase is for the same timestamp there is nothing to add
             // because we are using the block_timestamp, 
             // In real life situations it will be hard to enter this case
-            if last_element.timestamp == current_time{
-                self.twabs.push(&last_element);
-            } else {
+            if last_element.timestamp != current_time{
                 let twab_amount = self.compute_twab_balance(
                     last_element.amount, 
                     self.balance, 
                     current_time, 
                     last_element.timestamp
                 );
-                self.twabs.push(&last_element);
-                self.twabs.push(&Twab{timestamp: current_time, amount: twab_amount});
+                self.twabs.push(&Twab{timestamp: current_time, amount: twab_amount}, settings.max_cardinality, settings.prune_before);
             }
         }
     }
 
+    /// The balance that was held at `target_time`
+    fn balance_at(&self, target_time: u64) -> Balance{
+        let oldest_twab = match self.twabs.get(0) {
+            Some(twab) => twab,
+            None => return 0,
+        };
+        let newest_twab = self.twabs.last().unwrap_or_default();
+
+        if target_time < oldest_twab.timestamp{
+            self.assert_history_kept(target_time);
+            return 0;
+        }
+
+        if target_time >= newest_twab.timestamp{
+            return self.balance;
+        }
+
+        // the balance between two twabs is their amount difference divided by the time between them
+        let before_or_at = self.get_twab_before_or_at(target_time);
+        let after = self.get_twab_after_or_at(target_time + 1);
+
+        return ((after.amount - before_or_at.amount) / U256::from(after.timestamp - before_or_at.timestamp)).as_u128();
+    }
+
+    fn average_balance_between_timestamps(&self, start_time: u64, end_time: u64) -> Balance{
+        assert!(start_time < end_time);
+        let oldest_twab = self.twabs.get(0).unwrap_or_default();
+        let newest_twab = self.twabs.get(self.twabs.len() - 1).unwrap_or_default();
+
+        let start_twab: Twab = self.calculate_twab(&oldest_twab, &newest_twab, start_time);
+        let end_twab: Twab = self.calculate_twab(&oldest_twab, &newest_twab, end_time);
+            
+        return ((end_twab.amount - start_twab.amount) / U256::from(end_twab.timestamp - start_twab.timestamp)).as_u128();
+    }
+
     fn compute_twab_balance(
         &self, 
-        last_twab_amount: Balance, 
+        last_twab_amount: U256, 
         current_balance: Balance, 
         current_time: u64, 
         last_twab_timestamp: u64
-    ) -> Balance {
-        return last_twab_amount + current_balance * ((current_time - last_twab_timestamp) as u128);
+    ) -> U256 {
+        return last_twab_amount + U256::from(current_balance) * U256::from(current_time - last_twab_timestamp);
     }
 
 }
@@ -152,38 +313,78 @@ impl AccountBalance{
 pub struct AccountsDepositHistory{
     pub accounts: UnorderedMap<AccountId, AccountBalance>,
     pub total_supply: AccountBalance,
+    pub settings: TwabSettings,
 }
 
 impl Default for AccountsDepositHistory{
     fn default() -> Self {
-        AccountsDepositHistory { 
-            accounts: UnorderedMap::new(StorageKeys::AccountBalance),
-            total_supply: AccountBalance { 
-                balance: Balance::default(), 
-                twabs: Vector::new(StorageKeys::TotalSupplyAccountBalance) 
-            },
-        }
+        AccountsDepositHistory::new(StorageKeys::AccountBalance)
     }
 }
 
 impl AccountsDepositHistory {
+    /// The map of the accounts is stored under `accounts_prefix`, the twabs of every account under its own key
+    pub fn new<S: IntoStorageKey>(accounts_prefix: S) -> Self{
+        AccountsDepositHistory { 
+            accounts: UnorderedMap::new(accounts_prefix),
+            total_supply: AccountBalance::new(StorageKeys::TotalSupplyAccountBalance),
+            settings: TwabSettings::default(),
+        }
+    }
+
+    /// Drops the twabs of `account_id` that are older than needed
+    pub fn prune_account(&mut self, account_id: &AccountId){
+        if let Some(mut acc_balance) = self.accounts.get(&account_id){
+            acc_balance.twabs.prune_before(self.settings.prune_before);
+            self.accounts.insert(&account_id, &acc_balance);
+        }
+    }
+
+    pub fn prune_total_supply(&mut self){
+        self.total_supply.twabs.prune_before(self.settings.prune_before);
+    }
+
+    /// Every deposit adds a twab to the total supply, so its history isn't bounded by cardinality. 
+    /// Otherwise many small deposits could push the twabs of an open draw out.
+    /// It is only pruned with `prune_total_supply`
+    fn total_supply_settings(&self) -> TwabSettings{
+        return TwabSettings { max_cardinality: u64::MAX, prune_before: 0 };
+    }
+
+    fn account_prefix(account_id: &AccountId) -> Vec<u8>{
+        return StorageKeys::SubAccountBalance { 
+            account_hash: utils::utils::get_hash(&account_id) 
+        }.into_storage_key();
+    }
+
     fn get_account(&self, account_id: &AccountId) -> AccountBalance{
         return self.accounts.get(&account_id).unwrap_or_else(|| {
-            AccountBalance { 
-                balance: Balance::default(), 
-                twabs: Vector::new(
-                    StorageKeys::SubAccountBalance { 
-                        account_hash: utils::utils::get_hash(&account_id) 
-                    }) 
-            }
+            AccountBalance::new(Self::account_prefix(account_id))
         });
     }
+
+    pub fn insert_history(&mut self, account_id: &AccountId, balance: Balance, twabs: &[Twab]){
+        self.accounts.insert(&account_id, &AccountBalance::from_history(Self::account_prefix(account_id), balance, twabs));
+    }
+
+    pub fn insert_total_supply_history(&mut self, balance: Balance, twabs: &[Twab]){
+        self.total_supply = AccountBalance::from_history(StorageKeys::TotalSupplyAccountBalance, balance, twabs);
+    }
+
+    pub fn get_twabs(&self, account_id: &AccountId, from_index: u64, limit: u64) -> Vec<Twab>{
+        let acc_balance = self.get_account(account_id);
+        let to_index = std::cmp::min(from_index.saturating_add(limit), acc_balance.twabs.len());
+
+        return (from_index..to_index)
+            .map(|idx| acc_balance.twabs.get(idx).unwrap_or_default())
+            .collect();
+    }
 }
 
 impl ITwab for AccountsDepositHistory{
     fn increase_balance(&mut self, account: &AccountId, amount: Balance, current_time: u64) {
         let mut acc_balance = self.get_account(&account);
-        acc_balance.generate_twab(current_time);
+        acc_balance.generate_twab(current_time, &self.settings);
 
         acc_balance.balance += amount;
         self.accounts.insert(&account, &acc_balance);
@@ -191,19 +392,19 @@ impl ITwab for AccountsDepositHistory{
 
     fn decrease_balance(&mut self, account: &AccountId, amount: Balance, current_time: u64) {
         let mut acc_balance = self.get_account(&account);
-        acc_balance.generate_twab(current_time);
+        acc_balance.generate_twab(current_time, &self.settings);
         
         acc_balance.balance -= amount;
         self.accounts.insert(&account, &acc_balance);
     }
 
     fn increase_total_supply(&mut self, amount: Balance, current_time: u64) {
-        self.total_supply.generate_twab(current_time);
+        self.total_supply.generate_twab(current_time, &self.total_supply_settings());
         self.total_supply.balance += amount;
     }
 
     fn decrease_total_supply(&mut self, amount: Balance, current_time: u64) {
-        self.total_supply.generate_twab(current_time);
+        self.total_supply.generate_twab(current_time, &self.total_supply_settings());
         self.total_supply.balance -= amount;
     }
 
@@ -212,14 +413,7 @@ impl ITwab for AccountsDepositHistory{
         start_time: u64,
         end_time: u64
     ) -> Balance{
-        assert!(start_time < end_time);
-        let oldest_twab = self.total_supply.twabs.get(0).unwrap_or_default();
-        let newest_twab = self.total_supply.twabs.get(self.total_supply.twabs.len() - 1).unwrap_or_default();
-
-        let start_twab: Twab = self.total_supply.calculate_twab(&oldest_twab, &newest_twab, start_time);
-        let end_twab: Twab = self.total_supply.calculate_twab(&oldest_twab, &newest_twab, end_time);
-            
-        return (end_twab.amount - start_twab.amount)/((end_twab.timestamp - start_twab.timestamp) as u128);
+        return self.total_supply.average_balance_between_timestamps(start_time, end_time);
     }
 
     fn average_balance_between_timestamps(
@@ -228,14 +422,211 @@ impl ITwab for AccountsDepositHistory{
             start_time: u64, 
             end_time: u64
         ) -> Balance {
-        assert!(start_time < end_time);
-        let account_balance = self.accounts.get(&account).unwrap_or_default();
-        let oldest_twab = account_balance.twabs.get(0).unwrap_or_default();
-        let newest_twab = account_balance.twabs.get(account_balance.twabs.len() - 1).unwrap_or_default();
+        let account_balance = self.get_account(&account);
+        return account_balance.average_balance_between_timestamps(start_time, end_time);
+    }
 
-        let start_twab: Twab = account_balance.calculate_twab(&oldest_twab, &newest_twab, start_time);
-        let end_twab: Twab = account_balance.calculate_twab(&oldest_twab, &newest_twab, end_time);
-            
-        return (end_twab.amount - start_twab.amount)/((end_twab.timestamp - start_twab.timestamp) as u128);
+    fn balance_at(&self, account: &AccountId, target_time: u64) -> Balance {
+        return self.get_account(&account).balance_at(target_time);
+    }
+
+    fn total_supply_at(&self, target_time: u64) -> Balance {
+        return self.total_supply.balance_at(target_time);
+    }
+}
+
+/// Keeps the balances that decide the odds of winning.
+/// Every account delegates its tickets to itself, unless it chooses another delegate
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct AccountsDelegatedHistory{
+    pub delegates: UnorderedMap<AccountId, AccountId>,
+    pub accounts: UnorderedMap<AccountId, AccountBalance>,
+    pub settings: TwabSettings,
+}
+
+impl Default for AccountsDelegatedHistory{
+    fn default() -> Self {
+        AccountsDelegatedHistory { 
+            delegates: UnorderedMap::new(StorageKeys::Delegates),
+            accounts: UnorderedMap::new(StorageKeys::DelegatedAccountBalance),
+            settings: TwabSettings::default(),
+        }
+    }
+}
+
+impl AccountsDelegatedHistory {
+    /// Drops the delegated twabs of `account_id` that are older than needed
+    pub fn prune_account(&mut self, account_id: &AccountId){
+        if let Some(mut acc_balance) = self.accounts.get(&account_id){
+            acc_balance.twabs.prune_before(self.settings.prune_before);
+            self.accounts.insert(&account_id, &acc_balance);
+        }
+    }
+
+    fn account_prefix(account_id: &AccountId) -> Vec<u8>{
+        return StorageKeys::DelegatedSubAccountBalance { 
+            account_hash: utils::utils::get_hash(&account_id) 
+        }.into_storage_key();
+    }
+
+    fn get_account(&self, account_id: &AccountId) -> AccountBalance{
+        return self.accounts.get(&account_id).unwrap_or_else(|| {
+            AccountBalance::new(Self::account_prefix(account_id))
+        });
+    }
+
+    pub fn insert_history(&mut self, delegate_id: &AccountId, balance: Balance, twabs: &[Twab]){
+        self.accounts.insert(&delegate_id, &AccountBalance::from_history(Self::account_prefix(delegate_id), balance, twabs));
+    }
+
+    pub fn get_delegate(&self, account_id: &AccountId) -> AccountId{
+        return self.delegates.get(&account_id).unwrap_or_else(|| account_id.clone());
+    }
+
+    /// Moves the `balance` of tickets owned by `account_id` from its current delegate to `delegate_id`
+    pub fn set_delegate(&mut self, account_id: &AccountId, delegate_id: &AccountId, balance: Balance, current_time: u64){
+        let old_delegate_id = self.get_delegate(account_id);
+
+        if balance > 0 {
+            self.decrease_delegated_balance(&old_delegate_id, balance, current_time);
+            self.increase_delegated_balance(delegate_id, balance, current_time);
+        }
+
+        if delegate_id == account_id {
+            self.delegates.remove(&account_id);
+        } else {
+            self.delegates.insert(&account_id, &delegate_id);
+        }
+    }
+
+    fn increase_delegated_balance(&mut self, delegate_id: &AccountId, amount: Balance, current_time: u64){
+        let mut acc_balance = self.get_account(&delegate_id);
+        acc_balance.generate_twab(current_time, &self.settings);
+
+        acc_balance.balance += amount;
+        self.accounts.insert(&delegate_id, &acc_balance);
+    }
+
+    fn decrease_delegated_balance(&mut self, delegate_id: &AccountId, amount: Balance, current_time: u64){
+        let mut acc_balance = self.get_account(&delegate_id);
+        acc_balance.generate_twab(current_time, &self.settings);
+
+        acc_balance.balance -= amount;
+        self.accounts.insert(&delegate_id, &acc_balance);
+    }
+
+    /// Increases the delegated balance of the delegate of `account_id`
+    pub fn increase_balance(&mut self, account_id: &AccountId, amount: Balance, current_time: u64){
+        let delegate_id = self.get_delegate(account_id);
+        self.increase_delegated_balance(&delegate_id, amount, current_time);
+    }
+
+    /// Decreases the delegated balance of the delegate of `account_id`
+    pub fn decrease_balance(&mut self, account_id: &AccountId, amount: Balance, current_time: u64){
+        let delegate_id = self.get_delegate(account_id);
+        self.decrease_delegated_balance(&delegate_id, amount, current_time);
+    }
+
+    pub fn average_balance_between_timestamps(&self, account_id: &AccountId, start_time: u64, end_time: u64) -> Balance{
+        let account_balance = self.get_account(&account_id);
+        return account_balance.average_balance_between_timestamps(start_time, end_time);
+    }
+}
+
+#[near_bindgen]
+impl Contract{
+    /// Delegates the chance of winning of all caller's tickets to `delegate_id`,
+    /// without transferring the tickets. Delegating to itself revokes the delegation
+    #[payable]
+    pub fn delegate(&mut self, delegate_id: AccountId){
+        assert_one_yocto();
+        self.assert_tickets_migrated();
+        let account_id = env::predecessor_account_id();
+        let old_delegate_id = self.delegated_tickets.get_delegate(&account_id);
+
+        if old_delegate_id == delegate_id {
+            return;
+        }
+
+        let balance = self.token.accounts.get(&account_id).unwrap_or(0);
+        self.delegated_tickets.set_delegate(&account_id, &delegate_id, balance, env::block_timestamp_ms());
+
+        events::events::delegate_changed(&account_id, &old_delegate_id, &delegate_id);
+    }
+
+    pub fn get_delegate(&self, account_id: AccountId) -> AccountId{
+        return self.delegated_tickets.get_delegate(&account_id);
+    }
+
+    /// Timestamps are in milliseconds, like `env::block_timestamp_ms`
+    pub fn get_balance_at(&self, account_id: AccountId, timestamp: u64) -> U128{
+        return U128(self.tickets.balance_at(&account_id, timestamp));
+    }
+
+    /// Average balance of the account for every `(start_time, end_time)` window
+    pub fn get_average_balances_between(&self, account_id: AccountId, windows: Vec<(u64, u64)>) -> Vec<U128>{
+        return windows
+            .iter()
+            .map(|(start_time, end_time)| U128(self.tickets.average_balance_between_timestamps(&account_id, *start_time, *end_time)))
+            .collect();
+    }
+
+    pub fn get_total_supply_at(&self, timestamp: u64) -> U128{
+        return U128(self.tickets.total_supply_at(timestamp));
+    }
+
+    pub fn get_average_total_supplies_between(&self, windows: Vec<(u64, u64)>) -> Vec<U128>{
+        return windows
+            .iter()
+            .map(|(start_time, end_time)| U128(self.tickets.average_total_supply_between_timestamps(*start_time, *end_time)))
+            .collect();
+    }
+
+    /// Raw twabs of the account, `from_index` is counted from the oldest twab that is still kept
+    pub fn get_account_twabs(&self, account_id: AccountId, from_index: u64, limit: u64) -> Vec<TwabView>{
+        return self.tickets
+            .get_twabs(&account_id, from_index, limit)
+            .iter()
+            .map(|twab| TwabView { amount: twab.amount.to_string(), timestamp: twab.timestamp })
+            .collect();
+    }
+
+    pub (crate) fn set_twab_settings(&mut self, settings: TwabSettings){
+        self.tickets.settings = settings;
+        self.delegated_tickets.settings = settings;
+    }
+
+    pub fn get_twab_max_cardinality(&self) -> u64{
+        return self.tickets.settings.max_cardinality;
+    }
+
+    /// Twabs older than this timestamp are removed when the account changes or is pruned
+    pub fn get_twab_prune_before(&self) -> u64{
+        return self.tickets.settings.prune_before;
+    }
+
+    #[payable]
+    pub fn set_twab_max_cardinality(&mut self, max_cardinality: u64){
+        assert_one_yocto();
+        self.assert_role(Role::Admin);
+        // at least two twabs are needed to calculate the average between them
+        assert!(max_cardinality >= 2, "Max cardinality should be at least 2");
+
+        let mut settings = self.tickets.settings;
+        settings.max_cardinality = max_cardinality;
+        self.set_twab_settings(settings);
+    }
+
+    /// Removes the twabs of the given accounts that are older 
+    /// than the oldest prize distribution that can still be claimed
+    pub fn prune_twabs(&mut self, account_ids: Vec<AccountId>){
+        self.assert_role(Role::Keeper);
+        self.update_twab_prune_before();
+
+        self.tickets.prune_total_supply();
+        for account_id in account_ids.iter(){
+            self.tickets.prune_account(account_id);
+            self.delegated_tickets.prune_account(account_id);
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,320 @@
+use crate::*;
+use common::generic_ring_buffer::GenericRingBuffer;
+use common::types::U256;
+use interfaces::prize_distribution::{PrizeDistribution, MAX_TIERS};
+use near_sdk::collections::{UnorderedMap, Vector};
+use twab::{Twab, TwabBuffer, AccountBalance};
+use utils::storage_keys::StorageKeys;
+
+/// The layout of the state before the twabs were stored as U256.
+/// Every account stored its twabs under its own `SubAccountBalance` key
+mod v1{
+    use super::*;
+
+    pub const MAX_PRIZES_CAPACITY: usize = 32;
+    pub const TIERS_NOMINAL: u128 = 100;
+
+    #[derive(BorshDeserialize, BorshSerialize)]
+    pub struct Twab{
+        pub amount: Balance,
+        pub timestamp: u64,
+    }
+
+    #[derive(BorshDeserialize, BorshSerialize)]
+    pub struct AccountBalance{
+        pub balance: Balance,
+        pub twabs: Vector<Twab>,
+    }
+
+    #[derive(BorshDeserialize, BorshSerialize)]
+    pub struct AccountsDepositHistory{
+        pub accounts: UnorderedMap<AccountId, AccountBalance>,
+        pub total_supply: AccountBalance,
+    }
+
+    #[derive(BorshDeserialize, BorshSerialize, Clone, Copy, Default, PartialEq)]
+    pub struct PrizeDistribution{
+        pub number_of_picks: u64,
+        pub draw_id: u128,
+        pub cardinality: u8,
+        pub bit_range_size: u8,
+        pub tiers: [u32; MAX_TIERS],
+        pub prize: u128,
+        pub max_picks: u128,
+        pub start_time: u64,
+        pub end_time: u64,
+        pub winning_number: WinningNumber,
+    }
+
+    #[derive(BorshDeserialize, BorshSerialize)]
+    pub struct PrizeBuffer{
+        pub buffer: GenericRingBuffer<PrizeDistribution, MAX_PRIZES_CAPACITY>,
+    }
+
+    #[derive(BorshDeserialize, BorshSerialize)]
+    pub struct Contract{
+        pub token: FungibleToken,
+        pub metadata: LazyOption<FungibleTokenMetadata>,
+        pub deposited_token_id: AccountId,
+        pub tickets: AccountsDepositHistory,
+        pub prizes: PrizeBuffer,
+        pub draw_contract: AccountId,
+        pub acc_picks: AccountsPicks,
+        pub yield_source: YieldSource,
+    }
+}
+
+impl From<&v1::Twab> for Twab{
+    fn from(twab: &v1::Twab) -> Self {
+        Twab { amount: U256::from(twab.amount), timestamp: twab.timestamp }
+    }
+}
+
+impl From<v1::PrizeDistribution> for PrizeDistribution{
+    fn from(prize_distribution: v1::PrizeDistribution) -> Self {
+        if prize_distribution == v1::PrizeDistribution::default(){
+            return PrizeDistribution::default();
+        }
+
+        PrizeDistribution {
+            number_of_picks: prize_distribution.number_of_picks,
+            draw_id: prize_distribution.draw_id,
+            cardinality: prize_distribution.cardinality,
+            bit_range_size: prize_distribution.bit_range_size,
+            tiers: prize_distribution.tiers,
+            tiers_nominal: v1::TIERS_NOMINAL,
+            prize: prize_distribution.prize,
+            // the claims weren't recorded, so the old prizes can't be claimed again
+            claimed: prize_distribution.prize,
+            swept: true,
+            max_picks: prize_distribution.max_picks,
+            start_time: prize_distribution.start_time,
+            end_time: prize_distribution.end_time,
+            // the start of the draw wasn't kept, the twabs are kept from the beginning
+            draw_started_at: 0,
+            winning_number: prize_distribution.winning_number,
+        }
+    }
+}
+
+/// The tickets of the first version that are not converted yet. They are converted in batches
+/// by `migrate_accounts`, every operation of the pool is paused until all of them are
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct TicketsMigration{
+    /// The accounts that are left, they are removed once converted
+    accounts: UnorderedMap<AccountId, v1::AccountBalance>,
+    /// The total supply history is converted in place after the accounts, twab by twab
+    total_supply: v1::AccountBalance,
+    /// The number of total supply twabs converted so far
+    total_supply_cursor: u64,
+}
+
+impl TicketsMigration{
+    pub fn accounts_left(&self) -> u64{
+        return self.accounts.len();
+    }
+}
+
+#[near_bindgen]
+impl Contract{
+    /// Converts the state of the first version of the contract. The deposits are the principal
+    /// allocated to the old yield source.
+    /// The claims of the first version weren't recorded, so its prizes can't be claimed anymore.
+    /// They are forfeited and their amount is moved to the reserve, so it doesn't become yield again.
+    /// The accounts are converted afterwards with `migrate_accounts`
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate(owner_id: AccountId) -> Self{
+        let old: v1::Contract = env::state_read().expect("There is no state to migrate");
+        let total_principal = old.tickets.total_supply.balance;
+
+        let mut accounting = PoolAccounting::default();
+        accounting.on_deposit(total_principal);
+        accounting.reserve = old.prizes.buffer.arr.iter().map(|el| el.prize).sum();
+        let mut yield_sources = YieldSources::new(old.yield_source);
+        yield_sources.sources[0].allocated = total_principal;
+
+        return Self {
+            token: old.token,
+            metadata: old.metadata,
+            deposited_token_id: old.deposited_token_id,
+            // the old accounts are still stored under the default key
+            tickets: AccountsDepositHistory::new(StorageKeys::MigratedAccountBalance),
+            delegated_tickets: AccountsDelegatedHistory::default(),
+            prizes: PrizeBuffer { buffer: old.prizes.buffer.map(PrizeDistribution::from) },
+            prize_config: PrizeConfig::default(),
+            accounting: accounting,
+            draw_contract: old.draw_contract,
+            acc_picks: old.acc_picks,
+            claims: AccountsClaims::default(),
+            yield_sources: yield_sources,
+            migration: None,
+            farm_rewards: FarmRewards::default(),
+            access_control: AccessControl::new(owner_id, StorageKeys::Roles),
+            paused_operations: Vec::new(),
+            tickets_migration: Some(TicketsMigration {
+                accounts: old.tickets.accounts,
+                total_supply: old.tickets.total_supply,
+                total_supply_cursor: 0,
+            }),
+        };
+    }
+
+    /// The accounts of the first version that are not converted yet
+    pub fn get_accounts_to_migrate(&self) -> u64{
+        return self.tickets_migration.as_ref().map_or(0, |el| el.accounts_left());
+    }
+
+    /// Converts up to `limit` accounts of the first version, then up to `limit` twabs of the total supply.
+    /// The twabs are rewritten as U256 under the same keys and every account delegates to itself.
+    /// Returns true once everything is converted and the pool is resumed
+    pub fn migrate_accounts(&mut self, limit: u64) -> bool{
+        self.assert_role(Role::Keeper);
+        let mut migration = self.tickets_migration.take().expect("The accounts are already migrated");
+
+        let mut left = limit;
+        while left > 0 && !migration.accounts.is_empty(){
+            let account_id = migration.accounts.keys_as_vector().get(migration.accounts.len() - 1).unwrap();
+            let mut acc_balance = migration.accounts.remove(&account_id).unwrap();
+            let twabs: Vec<Twab> = acc_balance.twabs.iter().map(|el| Twab::from(&el)).collect();
+
+            // the new twabs have the same keys, they can't be added over the old entries
+            acc_balance.twabs.clear();
+            self.tickets.insert_history(&account_id, acc_balance.balance, &twabs);
+            self.delegated_tickets.insert_history(&account_id, acc_balance.balance, &twabs);
+            left -= 1;
+        }
+
+        let total_supply_len = migration.total_supply.twabs.len();
+        while left > 0 && migration.total_supply_cursor < total_supply_len{
+            let twab = Twab::from(&migration.total_supply.twabs.get(migration.total_supply_cursor).unwrap());
+            migration.total_supply.twabs.replace_raw(migration.total_supply_cursor, &twab.try_to_vec().unwrap());
+            migration.total_supply_cursor += 1;
+            left -= 1;
+        }
+
+        if !migration.accounts.is_empty() || migration.total_supply_cursor < total_supply_len{
+            self.tickets_migration = Some(migration);
+            return false;
+        }
+
+        self.tickets.total_supply = AccountBalance {
+            balance: migration.total_supply.balance,
+            twabs: TwabBuffer::from_stored(StorageKeys::TotalSupplyAccountBalance, total_supply_len),
+        };
+        log!("All accounts are migrated");
+        return true;
+    }
+}
+
+impl Contract{
+    /// The tickets can't move until the accounts of the first version are converted
+    pub (crate) fn assert_tickets_migrated(&self){
+        assert!(self.tickets_migration.is_none(), "The accounts are being migrated");
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::test_utils::{mmmm, sec, usdc, burrow, set_caller};
+    use crate::interfaces::prize_distribution::PrizeDistributionActor;
+    use near_contract_standards::fungible_token::core::FungibleTokenCore;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    fn old_account(account_id: &AccountId, balance: Balance, twabs: &[(Balance, u64)]) -> v1::AccountBalance{
+        let mut result = v1::AccountBalance {
+            balance: balance,
+            twabs: Vector::new(StorageKeys::SubAccountBalance { account_hash: utils::utils::get_hash(account_id) }),
+        };
+        for (amount, timestamp) in twabs.iter(){
+            result.twabs.push(&v1::Twab { amount: *amount, timestamp: *timestamp });
+        }
+
+        return result;
+    }
+
+    fn old_state() -> v1::Contract{
+        let mut tickets = v1::AccountsDepositHistory {
+            accounts: UnorderedMap::new(StorageKeys::AccountBalance),
+            total_supply: v1::AccountBalance { balance: 80, twabs: Vector::new(StorageKeys::TotalSupplyAccountBalance) },
+        };
+        // mmmm deposits 100 at 0 and withdraws 50 at 20, sec deposits 30 at 10
+        tickets.accounts.insert(&mmmm(), &old_account(&mmmm(), 50, &[(0, 0), (2000, 20)]));
+        tickets.accounts.insert(&sec(), &old_account(&sec(), 30, &[(0, 10)]));
+        for (amount, timestamp) in [(0, 0), (1000, 10), (2300, 20)]{
+            tickets.total_supply.twabs.push(&v1::Twab { amount: amount, timestamp: timestamp });
+        }
+
+        let mut prizes = v1::PrizeBuffer { buffer: GenericRingBuffer::new() };
+        prizes.buffer.arr[0].draw_id = 1;
+        prizes.buffer.arr[0].prize = 500;
+
+        return v1::Contract {
+            token: FungibleToken::new(b"a".to_vec()),
+            metadata: LazyOption::new(b"m".to_vec(), None),
+            deposited_token_id: usdc(),
+            tickets: tickets,
+            prizes: prizes,
+            draw_contract: sec(),
+            acc_picks: AccountsPicks::default(),
+            yield_source: YieldSource::Burrow { address: burrow() },
+        };
+    }
+
+    #[test]
+    fn check_migrate_from_v1(){
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(mmmm())
+            .predecessor_account_id(mmmm())
+            .build());
+        env::state_write(&old_state());
+
+        let mut contract = Contract::migrate(sec());
+        assert_eq!(contract.get_accounts_to_migrate(), 2);
+        assert!(contract.is_paused(Operation::Deposit));
+
+        // one account, then the other one and the total supply
+        set_caller(sec());
+        assert!(!contract.migrate_accounts(1));
+        assert_eq!(contract.get_accounts_to_migrate(), 1);
+        assert!(!contract.migrate_accounts(2));
+        assert_eq!(contract.get_accounts_to_migrate(), 0);
+        assert!(contract.migrate_accounts(2));
+        assert!(!contract.is_paused(Operation::Deposit));
+
+        assert_eq!(contract.tickets.average_balance_between_timestamps(&mmmm(), 0, 20), 100);
+        assert_eq!(contract.tickets.average_balance_between_timestamps(&mmmm(), 0, 40), 75);
+        assert_eq!(contract.tickets.balance_at(&sec(), 15), 30);
+        assert_eq!(contract.delegated_tickets.average_balance_between_timestamps(&mmmm(), 0, 40), 75);
+        assert_eq!(contract.delegated_tickets.average_balance_between_timestamps(&sec(), 0, 20), 15);
+        assert_eq!(contract.tickets.average_total_supply_between_timestamps(0, 20), 115);
+        assert_eq!(contract.tickets.accounts.len(), 2);
+
+        assert_eq!(contract.get_owner(), sec());
+        assert_eq!(contract.get_pool_accounting().total_principal.0, 80);
+        assert_eq!(contract.yield_sources.sources[0].allocated, 80);
+
+        let prize_distribution = contract.get_prize_distribution(1);
+        assert_eq!(prize_distribution.prize, 500);
+        assert_eq!(prize_distribution.claimed, 500);
+        // the prizes of the first version are forfeited to the reserve
+        assert_eq!(contract.get_prize_reserve(), U128(500));
+        assert!(contract.prizes.buffer.arr[1] == PrizeDistribution::default());
+    }
+
+    #[test]
+    #[should_panic(expected = "The accounts are being migrated")]
+    fn test_transfer_before_accounts_migrated(){
+        testing_env!(VMContextBuilder::new()
+            .current_account_id(mmmm())
+            .predecessor_account_id(mmmm())
+            .build());
+        env::state_write(&old_state());
+        let mut contract = Contract::migrate(sec());
+
+        set_caller(mmmm());
+        contract.ft_transfer(sec(), U128(10), None);
+    }
+}
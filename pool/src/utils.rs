@@ -10,12 +10,23 @@ pub mod storage_keys{
         TotalSupplyAccountBalance,
         AccountPicks,
         AccountDrawPicks {account_hash: CryptoHash},
+        Delegates,
+        DelegatedAccountBalance,
+        DelegatedSubAccountBalance {account_hash: CryptoHash},
+        Roles,
+        AccountClaims,
+        AccountDrawClaims {account_hash: CryptoHash},
+        YieldHarvested,
+        HarvestedFarmRewards,
+        FarmRewardsInDex,
+        MigratedAccountBalance,
     }
 }
 
 pub mod utils{
     use common::types::{NumPicks, WinningNumber};
-    use near_sdk::{AccountId, CryptoHash};
+    use near_sdk::{AccountId, CryptoHash, Balance, PromiseResult};
+    use near_sdk::json_types::U128;
     use near_sdk::env::{self, keccak256_array};
 
     pub(crate) fn get_hash(account_id: &AccountId) -> CryptoHash {
@@ -31,6 +42,32 @@ pub mod utils{
         let arr=[bytes, &pick.to_be_bytes()].concat();
         return keccak256_array(&arr);
     } 
+
+    /// The part of `amount` that the deposit to a yield source used, read from the result of the deposit.
+    /// The token transfer returns the used amount, the other deposits return nothing
+    pub(crate) fn deposited_amount(amount: Balance) -> Balance{
+        return match env::promise_result(0){
+            PromiseResult::Successful(value) if value.is_empty() => amount,
+            PromiseResult::Successful(value) =>
+                near_sdk::serde_json::from_slice::<U128>(&value).map_or(amount, |el| el.0),
+            _ => 0,
+        };
+    }
+
+    /// The tokens a redeem from a yield source brought to the pool, `None` if the redeem failed.
+    /// Meta Pool returns the unstaked amount, the other yield sources redeem exactly `amount`
+    pub(crate) fn redeemed_amount(amount: Balance) -> Option<Balance>{
+        return redeemed_amount_at(0, amount);
+    }
+
+    /// The redeemed amount of the `idx` promise in a joint promise of redeems
+    pub(crate) fn redeemed_amount_at(idx: u64, amount: Balance) -> Option<Balance>{
+        return match env::promise_result(idx){
+            PromiseResult::Successful(value) =>
+                Some(near_sdk::serde_json::from_slice::<U128>(&value).map_or(amount, |el| el.0)),
+            _ => None,
+        };
+    }
 }
 
 pub mod gas{
@@ -44,6 +81,34 @@ pub mod gas{
     pub const GAS_FOR_FT_TRANSFER_CALL: Gas = Gas(300_000_000_000_000);
     pub const GAS_FOR_AFTER_FT_TRANSFER: Gas = Gas(Gas::ONE_TERA.0 * 20);
     pub const GAS_FOR_TRANSFER_TO_DEFI:Gas = Gas(Gas::ONE_TERA.0 * 100);
+    pub const GAS_FOR_WITHDRAW_FROM_DEFI: Gas = Gas(Gas::ONE_TERA.0 * 80);
+    pub const GAS_FOR_AFTER_WITHDRAW_FROM_DEFI: Gas = Gas(Gas::ONE_TERA.0 * 40);
+
+    pub const GAS_FOR_METAPOOL_CALL: Gas = Gas(Gas::ONE_TERA.0 * 10);
+    pub const GAS_FOR_METAPOOL_STAKE: Gas = Gas(Gas::ONE_TERA.0 * 25);
+    pub const GAS_FOR_LIQUID_UNSTAKE: Gas = Gas(Gas::ONE_TERA.0 * 25);
+    pub const GAS_FOR_AFTER_NEAR_UNWRAP: Gas = Gas(Gas::ONE_TERA.0 * 70);
+    pub const GAS_FOR_AFTER_METAPOOL_STAKE: Gas = Gas(Gas::ONE_TERA.0 * 30);
+    pub const GAS_FOR_AFTER_LIQUID_UNSTAKE: Gas = Gas(Gas::ONE_TERA.0 * 40);
+    pub const GAS_FOR_AFTER_NEAR_WRAP: Gas = Gas(Gas::ONE_TERA.0 * 10);
+    pub const GAS_FOR_METAPOOL_UNSTAKE: Gas = Gas(Gas::ONE_TERA.0 * 80);
+
+    pub const GAS_FOR_MIGRATION_REDEEM: Gas = Gas(Gas::ONE_TERA.0 * 210);
+    pub const GAS_FOR_MIGRATION_DEPOSIT: Gas = Gas(Gas::ONE_TERA.0 * 120);
+    pub const GAS_FOR_AFTER_DEPOSIT_TO_YIELD_SOURCE: Gas = Gas(Gas::ONE_TERA.0 * 10);
+    pub const GAS_FOR_REBALANCE_DEPOSIT: Gas = Gas(Gas::ONE_TERA.0 * 120);
+    pub const GAS_FOR_AFTER_REDEEM_FOR_IDLE_BUFFER: Gas = Gas(Gas::ONE_TERA.0 * 10);
+
+    pub const GAS_FOR_FARM_CLAIM: Gas = Gas(Gas::ONE_TERA.0 * 30);
+    pub const GAS_FOR_AFTER_FARM_CLAIM: Gas = Gas(Gas::ONE_TERA.0 * 110);
+    pub const GAS_FOR_AFTER_FARM_WITHDRAW: Gas = Gas(Gas::ONE_TERA.0 * 10);
+    pub const GAS_FOR_SEND_TO_DEX: Gas = Gas(Gas::ONE_TERA.0 * 50);
+    pub const GAS_FOR_AFTER_SEND_TO_DEX: Gas = Gas(Gas::ONE_TERA.0 * 110);
+    pub const GAS_FOR_DEX_SWAP: Gas = Gas(Gas::ONE_TERA.0 * 20);
+    pub const GAS_FOR_AFTER_DEX_SWAP: Gas = Gas(Gas::ONE_TERA.0 * 70);
+    pub const GAS_FOR_DEX_WITHDRAW: Gas = Gas(Gas::ONE_TERA.0 * 30);
+    pub const GAS_FOR_DEX_GET_DEPOSIT: Gas = Gas(Gas::ONE_TERA.0 * 10);
+    pub const GAS_FOR_AFTER_DEX_WITHDRAW: Gas = Gas(Gas::ONE_TERA.0 * 10);
     
     pub const MAX_GAS: Gas = Gas(300_000_000_000_000);
 }
\ No newline at end of file
@@ -0,0 +1,764 @@
+use crate::*;
+use crate::events::events;
+use common::types::U256;
+use near_sdk::PromiseResult;
+
+pub const MAX_YIELD_SOURCES: usize = 4;
+pub const DEFAULT_YIELD_SOURCE_WEIGHT: u32 = 100;
+/// Moves smaller than this part of the allocated tokens are not worth a rebalance, in basis points
+const REBALANCE_THRESHOLD_BPS: u128 = 10;
+const BPS_NOMINAL: u128 = 10_000;
+
+fn is_worth_rebalancing(amount: Balance, total: Balance) -> bool{
+    return amount > 0 && amount * BPS_NOMINAL >= total * REBALANCE_THRESHOLD_BPS;
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Clone)]
+pub struct WeightedYieldSource{
+    pub source: YieldSource,
+    /// The share of the deposits that should be in the yield source, relative to the other weights
+    pub weight: u32,
+    /// The tokens the pool has put in the yield source minus the tokens it has taken out
+    pub allocated: Balance,
+    /// The balance above the allocation when the last prize distribution was added,
+    /// minus the prizes redeemed from the yield source since
+    pub unpaid_yield: Balance,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WeightedYieldSourceView{
+    pub source: YieldSource,
+    pub weight: u32,
+    pub allocated: U128,
+    pub target: U128,
+}
+
+/// The yield sources the deposits are split across by weight
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct YieldSources{
+    pub sources: Vec<WeightedYieldSource>,
+    /// Tokens held by the pool, they pay out the small withdrawals and the prizes without a call to a yield source
+    pub idle: Balance,
+    /// The part of the principal that should be idle, in basis points
+    pub idle_buffer_bps: u32,
+    /// Redeems from the yield sources whose callbacks haven't run yet
+    pub pending_redeems: u32,
+    /// The number of redeems started so far. A position read while a redeem was started is outdated
+    pub redeems_started: u64,
+    /// The NEAR that Meta Pool sent to the pool and that failed to be wrapped.
+    /// It is neither idle nor in a yield source until `wrap_unstaked_near` wraps it
+    pub unwrapped_near: Balance,
+}
+
+impl YieldSources{
+    pub fn new(source: YieldSource) -> Self{
+        Self {
+            sources: vec![WeightedYieldSource { source: source, weight: DEFAULT_YIELD_SOURCE_WEIGHT, allocated: 0, unpaid_yield: 0 }],
+            idle: 0,
+            idle_buffer_bps: 0,
+            pending_redeems: 0,
+            redeems_started: 0,
+            unwrapped_near: 0,
+        }
+    }
+
+    /// Until the redeem ends the yield source still holds the tokens that are taken from its allocation
+    pub fn start_redeem(&mut self){
+        self.pending_redeems += 1;
+        self.redeems_started += 1;
+    }
+
+    pub fn end_redeem(&mut self){
+        self.pending_redeems = self.pending_redeems.saturating_sub(1);
+    }
+
+    /// No redeem is pending and none was started since `redeems_started`
+    pub fn is_settled_since(&self, redeems_started: u64) -> bool{
+        return self.pending_redeems == 0 && self.redeems_started == redeems_started;
+    }
+
+    /// The idle tokens the pool should hold for `total_principal`
+    pub fn idle_target(&self, total_principal: Balance) -> Balance{
+        return (U256::from(total_principal) * U256::from(self.idle_buffer_bps) / U256::from(BPS_NOMINAL)).as_u128();
+    }
+
+    /// The tokens of the pool that are not in a yield source
+    pub fn held(&self) -> Balance{
+        return self.idle + self.unwrapped_near;
+    }
+
+    /// Takes `amount` out of the idle tokens if they cover it
+    pub fn take_idle(&mut self, amount: Balance) -> bool{
+        if amount > self.idle{
+            return false;
+        }
+
+        self.idle -= amount;
+        return true;
+    }
+
+    pub fn find(&self, source: &YieldSource) -> Option<usize>{
+        return self.sources.iter().position(|el| &el.source == source);
+    }
+
+    pub fn total_weight(&self) -> u128{
+        return self.sources.iter().map(|el| u128::from(el.weight)).sum();
+    }
+
+    pub fn total_allocated(&self) -> Balance{
+        return self.sources.iter().map(|el| el.allocated).sum();
+    }
+
+    /// The part of `total` that should be in the yield source
+    pub fn target(&self, idx: usize, total: Balance) -> Balance{
+        let total_weight = self.total_weight();
+        if total_weight == 0{
+            return 0;
+        }
+
+        return (U256::from(total) * U256::from(self.sources[idx].weight) / U256::from(total_weight)).as_u128();
+    }
+
+    /// The yield source that is the most below its target after `amount` is deposited
+    pub fn deposit_index(&self, amount: Balance) -> usize{
+        let total = self.total_allocated() + amount;
+
+        return (0..self.sources.len())
+            .max_by_key(|idx| (self.target(*idx, total).saturating_sub(self.sources[*idx].allocated), self.sources[*idx].weight))
+            .expect("There is no yield source");
+    }
+
+    /// The most over-allocated yield source that can cover `amount`, if any
+    pub fn withdraw_index(&self, amount: Balance) -> Option<usize>{
+        let total = self.total_allocated();
+
+        return (0..self.sources.len())
+            .filter(|idx| self.sources[*idx].allocated >= amount)
+            .max_by_key(|idx| (self.sources[*idx].allocated.saturating_sub(self.target(*idx, total)), self.sources[*idx].allocated));
+    }
+
+    /// The most tokens that one yield source can pay out
+    pub fn max_allocated(&self) -> Balance{
+        return self.sources.iter().map(|el| el.allocated).max().unwrap_or(0);
+    }
+
+    /// Records the yield of every yield source from its balance, in the order of the yield sources
+    pub fn set_unpaid_yields(&mut self, balances: &Vec<Balance>){
+        for (el, balance) in self.sources.iter_mut().zip(balances.iter()){
+            el.unpaid_yield = balance.saturating_sub(el.allocated);
+        }
+    }
+
+    /// Splits the prize that the idle tokens don't cover across the yield sources in proportion
+    /// to their unpaid yield, at most all of it. The prizes come from the yield, so the allocations stay the same.
+    /// Returns what to redeem from every yield source and takes it out of the unpaid yield
+    pub fn prize_redeems(&mut self, amount: Balance) -> Vec<(YieldSource, Balance)>{
+        let total: Balance = self.sources.iter().map(|el| el.unpaid_yield).sum();
+        if total == 0{
+            return vec![];
+        }
+
+        let to_redeem = std::cmp::min(amount, total);
+        let mut redeems: Vec<Balance> = self.sources
+            .iter()
+            .map(|el| (U256::from(to_redeem) * U256::from(el.unpaid_yield) / U256::from(total)).as_u128())
+            .collect();
+
+        // the rounding leftover goes to the yield sources with unpaid yield left
+        let mut left = to_redeem - redeems.iter().sum::<Balance>();
+        for (idx, el) in self.sources.iter().enumerate(){
+            let extra = std::cmp::min(el.unpaid_yield - redeems[idx], left);
+            redeems[idx] += extra;
+            left -= extra;
+        }
+
+        let mut result = Vec::new();
+        for (idx, el) in self.sources.iter_mut().enumerate(){
+            if redeems[idx] > 0{
+                el.unpaid_yield -= redeems[idx];
+                result.push((el.source.clone(), redeems[idx]));
+            }
+        }
+
+        return result;
+    }
+
+    /// Gives back the unpaid yield of a prize redeem that failed
+    pub fn restore_unpaid_yield(&mut self, source: &YieldSource, amount: Balance){
+        if let Some(idx) = self.find(source){
+            self.sources[idx].unpaid_yield += amount;
+        }
+    }
+
+    /// Moves the tokens from the most over-allocated to the most under-allocated yield source
+    pub fn rebalance_move(&self) -> Option<(usize, usize, Balance)>{
+        let total = self.total_allocated();
+        let over = |idx: &usize| self.sources[*idx].allocated.saturating_sub(self.target(*idx, total));
+        let under = |idx: &usize| self.target(*idx, total).saturating_sub(self.sources[*idx].allocated);
+
+        let from = (0..self.sources.len()).max_by_key(over)?;
+        let to = (0..self.sources.len()).max_by_key(under)?;
+        let amount = std::cmp::min(over(&from), under(&to));
+
+        if !is_worth_rebalancing(amount, total){
+            return None;
+        }
+
+        return Some((from, to, amount));
+    }
+
+    /// Adds to the allocation of the yield source, if it is still in the list
+    pub fn credit(&mut self, source: &YieldSource, amount: Balance){
+        if let Some(idx) = self.find(source){
+            self.sources[idx].allocated += amount;
+        }
+    }
+
+    pub fn debit(&mut self, source: &YieldSource, amount: Balance){
+        if let Some(idx) = self.find(source){
+            self.sources[idx].allocated = self.sources[idx].allocated.saturating_sub(amount);
+        }
+    }
+
+    /// The allocation is debited by `amount` before a redeem. A Meta Pool redeem burns stNEAR
+    /// worth more than `amount`, so the tokens redeemed above it are debited as well
+    pub fn debit_surplus(&mut self, source: &YieldSource, amount: Balance, redeemed: Balance){
+        if redeemed > amount{
+            self.debit(source, redeemed - amount);
+        }
+    }
+
+    /// Picks the yield source for a deposit and adds `amount` to its allocation
+    pub fn allocate_deposit(&mut self, amount: Balance) -> YieldSource{
+        let idx = self.deposit_index(amount);
+        self.sources[idx].allocated += amount;
+        return self.sources[idx].source.clone();
+    }
+
+    /// Picks the yield source for a withdrawal and takes `amount` from its allocation.
+    /// A withdrawal is taken from one yield source, so it can't be more than the largest allocation
+    pub fn allocate_withdrawal(&mut self, amount: Balance) -> YieldSource{
+        let idx = self.withdraw_index(amount).unwrap_or_else(||
+            panic!("No yield source can cover {}, at most {} can be withdrawn at once", amount, self.max_allocated()));
+        self.sources[idx].allocated -= amount;
+        return self.sources[idx].source.clone();
+    }
+}
+
+#[near_bindgen]
+impl Contract{
+    pub fn get_yield_sources(&self) -> Vec<WeightedYieldSourceView>{
+        let total = self.yield_sources.total_allocated();
+
+        return self.yield_sources.sources
+            .iter()
+            .enumerate()
+            .map(|(idx, el)| WeightedYieldSourceView {
+                source: el.source.clone(),
+                weight: el.weight,
+                allocated: U128(el.allocated),
+                target: U128(self.yield_sources.target(idx, total)),
+            })
+            .collect();
+    }
+
+    pub fn get_idle_balance(&self) -> U128{
+        return U128(self.yield_sources.idle);
+    }
+
+    /// The most that can be withdrawn with one `withdraw`
+    pub fn get_max_withdrawal(&self) -> U128{
+        return U128(std::cmp::max(self.yield_sources.idle, self.yield_sources.max_allocated()));
+    }
+
+    pub fn get_idle_buffer_bps(&self) -> u32{
+        return self.yield_sources.idle_buffer_bps;
+    }
+
+    /// Sets the part of the principal that is kept idle in the pool. The deposits fill up the buffer,
+    /// the rebalances top it up or drain it to the yield sources
+    #[payable]
+    pub fn set_idle_buffer_bps(&mut self, idle_buffer_bps: u32){
+        assert_one_yocto();
+        self.assert_role(Role::Admin);
+        assert!(u128::from(idle_buffer_bps) <= BPS_NOMINAL, "The idle buffer can be at most 100%");
+
+        events::idle_buffer_updated(self.yield_sources.idle_buffer_bps, idle_buffer_bps);
+        self.yield_sources.idle_buffer_bps = idle_buffer_bps;
+    }
+
+    /// Adds a yield source without tokens, the next deposits and rebalances fill it up to its weight
+    #[payable]
+    pub fn add_yield_source(&mut self, source: YieldSource, weight: u32){
+        assert_one_yocto();
+        self.assert_role(Role::Admin);
+        assert!(self.yield_sources.find(&source).is_none(), "The yield source is already added");
+        assert!(self.yield_sources.sources.len() < MAX_YIELD_SOURCES, "There can be at most {} yield sources", MAX_YIELD_SOURCES);
+
+        events::yield_source_weight_updated(&source, 0, weight);
+        self.yield_sources.sources.push(WeightedYieldSource { source: source, weight: weight, allocated: 0, unpaid_yield: 0 });
+    }
+
+    /// With weight 0 the rebalances take all tokens out of the yield source
+    #[payable]
+    pub fn set_yield_source_weight(&mut self, source: YieldSource, weight: u32){
+        assert_one_yocto();
+        self.assert_role(Role::Admin);
+        let idx = self.yield_sources.find(&source).expect("There is no such yield source");
+
+        events::yield_source_weight_updated(&source, self.yield_sources.sources[idx].weight, weight);
+        self.yield_sources.sources[idx].weight = weight;
+        assert!(self.yield_sources.total_weight() > 0, "At least one yield source should have a weight");
+    }
+
+    /// Only a yield source without tokens allocated to it can be removed
+    #[payable]
+    pub fn remove_yield_source(&mut self, source: YieldSource){
+        assert_one_yocto();
+        self.assert_role(Role::Admin);
+        assert!(self.migration.is_none(), "A yield source migration is in progress");
+        let idx = self.yield_sources.find(&source).expect("There is no such yield source");
+        assert_eq!(self.yield_sources.sources[idx].allocated, 0, "The yield source still has tokens allocated to it");
+
+        self.yield_sources.sources.remove(idx);
+        assert!(self.yield_sources.total_weight() > 0, "At least one yield source should have a weight");
+        events::yield_source_removed(&source);
+    }
+
+    /// Brings the idle tokens to their target first, by depositing the tokens above it or by redeeming
+    /// the missing tokens from the most over-allocated yield source.
+    /// Otherwise moves tokens from the most over-allocated to the most under-allocated yield source
+    pub fn rebalance(&mut self) -> Promise{
+        self.assert_role(Role::Keeper);
+        self.assert_not_paused(Operation::YieldTransfer);
+
+        let idle_target = self.yield_sources.idle_target(self.accounting.total_principal);
+        let total = self.yield_sources.total_allocated() + self.yield_sources.idle;
+        let excess = self.yield_sources.idle.saturating_sub(idle_target);
+        if is_worth_rebalancing(excess, total){
+            self.yield_sources.idle -= excess;
+            let to = self.yield_sources.allocate_deposit(excess);
+
+            return self.deposit_for_rebalance(None, to, excess);
+        }
+
+        // one yield source tops up the idle tokens
+        let missing = std::cmp::min(idle_target.saturating_sub(self.yield_sources.idle), self.yield_sources.max_allocated());
+        if is_worth_rebalancing(missing, total){
+            let from = self.yield_sources.allocate_withdrawal(missing);
+            self.yield_sources.start_redeem();
+
+            return self
+                .get_yield_source(&from)
+                .redeem(&self.deposited_token_id, missing)
+                .then(this_contract::on_redeemed_for_idle_buffer(from, missing, env::current_account_id(), 0, gas::GAS_FOR_AFTER_REDEEM_FOR_IDLE_BUFFER));
+        }
+
+        let (from_idx, to_idx, amount) = self.yield_sources.rebalance_move().expect("The yield sources are balanced");
+        let from = self.yield_sources.sources[from_idx].source.clone();
+        let to = self.yield_sources.sources[to_idx].source.clone();
+        self.yield_sources.debit(&from, amount);
+        self.yield_sources.start_redeem();
+
+        return self
+            .get_yield_source(&from)
+            .redeem(&self.deposited_token_id, amount)
+            .then(this_contract::on_redeemed_for_rebalance(from, to, amount, env::current_account_id(), 0, gas::GAS_FOR_REBALANCE_DEPOSIT));
+    }
+
+    #[private]
+    pub fn on_redeemed_for_idle_buffer(&mut self, from: YieldSource, amount: Balance){
+        self.yield_sources.end_redeem();
+        let redeemed = match utils::utils::redeemed_amount(amount){
+            Some(redeemed) => redeemed,
+            None => {
+                log!("Error when redeeming {} for the idle buffer", amount);
+                self.yield_sources.credit(&from, amount);
+                return;
+            }
+        };
+
+        self.yield_sources.debit_surplus(&from, amount, redeemed);
+        self.yield_sources.idle += redeemed;
+        events::yield_sources_rebalanced(Some(&from), None, redeemed);
+    }
+
+    /// Deposits the redeemed tokens in the other yield source, they can differ from `amount`
+    #[private]
+    pub fn on_redeemed_for_rebalance(&mut self, from: YieldSource, to: YieldSource, amount: Balance) -> PromiseOrValue<()>{
+        let redeemed = match utils::utils::redeemed_amount(amount){
+            Some(redeemed) => redeemed,
+            None => {
+                log!("Error when redeeming {} for the rebalance", amount);
+                self.yield_sources.end_redeem();
+                self.yield_sources.credit(&from, amount);
+                return PromiseOrValue::Value(());
+            }
+        };
+        if redeemed == 0{
+            self.yield_sources.end_redeem();
+            return PromiseOrValue::Value(());
+        }
+
+        self.yield_sources.debit_surplus(&from, amount, redeemed);
+        self.yield_sources.credit(&to, redeemed);
+        return PromiseOrValue::Promise(self.deposit_for_rebalance(Some(from), to, redeemed));
+    }
+
+    #[private]
+    pub fn on_deposited_for_rebalance(&mut self, from: Option<YieldSource>, to: YieldSource, amount: Balance){
+        // the moved tokens are out of the yield sources until they are deposited
+        if from.is_some(){
+            self.yield_sources.end_redeem();
+        }
+
+        let deposited = utils::utils::deposited_amount(amount);
+        if deposited < amount{
+            log!("{} is not deposited in the yield source, it is kept in the pool", amount - deposited);
+            self.yield_sources.debit(&to, amount - deposited);
+            self.yield_sources.idle += amount - deposited;
+        }
+
+        events::yield_sources_rebalanced(from.as_ref(), Some(&to), deposited);
+    }
+
+    /// Keeps the part of the deposit that the idle buffer is missing in the pool
+    /// and deposits the rest in the yield source that is the most below its weight
+    pub (crate) fn deposit_to_yield_sources(&mut self, token_id: &AccountId, amount: Balance){
+        let idle_target = self.yield_sources.idle_target(self.accounting.total_principal);
+        let to_idle = std::cmp::min(idle_target.saturating_sub(self.yield_sources.idle), amount);
+        self.yield_sources.idle += to_idle;
+        if to_idle == amount{
+            return;
+        }
+
+        let source = self.yield_sources.allocate_deposit(amount - to_idle);
+        self
+            .get_yield_source(&source)
+            .transfer(token_id, amount - to_idle)
+            .then(this_contract::on_deposited_to_yield_source(source, amount - to_idle, env::current_account_id(), 0, gas::GAS_FOR_AFTER_DEPOSIT_TO_YIELD_SOURCE));
+    }
+
+    /// The tokens of a deposit that the yield source didn't use stay in the pool
+    #[private]
+    pub fn on_deposited_to_yield_source(&mut self, source: YieldSource, amount: Balance){
+        let deposited = utils::utils::deposited_amount(amount);
+        if deposited < amount{
+            log!("{} is not deposited in the yield source, it is kept in the pool", amount - deposited);
+            self.yield_sources.debit(&source, amount - deposited);
+            self.yield_sources.idle += amount - deposited;
+        }
+    }
+
+    /// Asks every yield source for its balance. The callback of the joint promise
+    /// gets the balances with `get_yield_sources_balance_result`
+    pub (crate) fn get_yield_sources_balance(&self) -> Promise{
+        let current_account_id = env::current_account_id();
+
+        return self.yield_sources.sources
+            .iter()
+            .map(|el| self.get_yield_source(&el.source).get_balance(&current_account_id))
+            .reduce(|acc, el| acc.and(el))
+            .expect("There is no yield source");
+    }
+
+    /// The sum of the balances in the yield sources and the tokens held by the pool,
+    /// `None` if any of the balances is missing
+    pub (crate) fn get_yield_sources_balance_result(&self) -> Option<Balance>{
+        return Some(self.get_yield_sources_position_result()? + self.yield_sources.held());
+    }
+
+    /// The sum of the balances in the yield sources, `None` if any of them is missing
+    pub (crate) fn get_yield_sources_position_result(&self) -> Option<Balance>{
+        return Some(self.get_yield_sources_balances_result()?.iter().sum());
+    }
+
+    /// The balance in every yield source, `None` if any of them is missing
+    pub (crate) fn get_yield_sources_balances_result(&self) -> Option<Vec<Balance>>{
+        let mut balances = Vec::new();
+        for idx in 0..env::promise_results_count(){
+            let balance = match env::promise_result(idx){
+                PromiseResult::Successful(value) => near_sdk::serde_json::from_slice::<Balance>(&value).ok()?,
+                _ => return None,
+            };
+            balances.push(balance);
+        }
+
+        return Some(balances);
+    }
+
+    /// Returns the pool balance in every yield source, without the idle tokens
+    pub (crate) fn get_pool_position(&self) -> Promise{
+        return self
+            .get_yield_sources_balance()
+            .then(this_contract::on_get_pool_position(env::current_account_id(), 0, gas::GET_BALANCE_FROM_DEFI));
+    }
+
+    #[private]
+    pub fn on_get_pool_position(&self) -> Vec<Balance>{
+        return self.get_yield_sources_balances_result()
+            .unwrap_or_else(|| panic!("Error when getting the balance from the yield sources"));
+    }
+
+    fn deposit_for_rebalance(&mut self, from: Option<YieldSource>, to: YieldSource, amount: Balance) -> Promise{
+        return self
+            .get_yield_source(&to)
+            .transfer(&self.deposited_token_id, amount)
+            .then(this_contract::on_deposited_for_rebalance(from, to, amount, env::current_account_id(), 0, gas::GAS_FOR_AFTER_DEPOSIT_TO_YIELD_SOURCE));
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use crate::*;
+    use crate::test_utils::{get_contract, mmmm, sec, burrow, usdc, set_context, set_promise_results, returned};
+    use near_sdk::PromiseResult;
+
+    use super::{YieldSources, WeightedYieldSource};
+
+    fn source(name: &str) -> YieldSource{
+        return YieldSource::Burrow { address: AccountId::new_unchecked(name.to_string()) };
+    }
+
+    fn yield_sources(allocations: Vec<(u32, Balance)>) -> YieldSources{
+        let sources = allocations
+            .iter()
+            .enumerate()
+            .map(|(idx, (weight, allocated))| WeightedYieldSource { source: source(&format!("source{}", idx)), weight: *weight, allocated: *allocated, unpaid_yield: 0 })
+            .collect();
+
+        return YieldSources { sources: sources, idle: 0, idle_buffer_bps: 0, pending_redeems: 0, redeems_started: 0, unwrapped_near: 0 };
+    }
+
+    #[test]
+    fn test_deposit_routing(){
+        // the targets are 60 and 40
+        let mut sources = yield_sources(vec![(60, 60), (40, 30)]);
+        assert_eq!(sources.deposit_index(10), 1);
+        assert_eq!(sources.allocate_deposit(10), source("source1"));
+        // the targets are 66 and 44
+        assert_eq!(sources.deposit_index(10), 0);
+
+        // a yield source without weight gets nothing
+        let sources = yield_sources(vec![(0, 0), (40, 40)]);
+        assert_eq!(sources.deposit_index(10), 1);
+    }
+
+    #[test]
+    fn test_withdraw_routing(){
+        // the targets are 66 and 44, the second one is over-allocated
+        let mut sources = yield_sources(vec![(60, 60), (40, 50)]);
+        assert_eq!(sources.withdraw_index(10), Some(1));
+        // only the first one can cover the amount
+        assert_eq!(sources.withdraw_index(55), Some(0));
+        // none can cover the amount
+        assert_eq!(sources.withdraw_index(500), None);
+
+        assert_eq!(sources.allocate_withdrawal(10), source("source1"));
+        assert_eq!(sources.sources[1].allocated, 40);
+    }
+
+    #[test]
+    fn test_prize_redeems(){
+        // the yields are 30 and 10
+        let mut sources = yield_sources(vec![(50, 100), (50, 300)]);
+        sources.set_unpaid_yields(&vec![130, 310]);
+        assert_eq!(sources.prize_redeems(20), vec![(source("source0"), 15), (source("source1"), 5)]);
+        // at most the unpaid yield is redeemed
+        assert_eq!(sources.prize_redeems(100), vec![(source("source0"), 15), (source("source1"), 5)]);
+        assert_eq!(sources.prize_redeems(10), vec![]);
+        assert_eq!(sources.sources[0].allocated, 100);
+
+        // the rounding leftover goes to the first yield source with yield left
+        sources.set_unpaid_yields(&vec![101, 301]);
+        assert_eq!(sources.prize_redeems(1), vec![(source("source0"), 1)]);
+        sources.restore_unpaid_yield(&source("source0"), 1);
+        assert_eq!(sources.sources[0].unpaid_yield, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "No yield source can cover 70, at most 60 can be withdrawn at once")]
+    fn test_withdrawal_over_largest_allocation(){
+        let mut contract = get_contract();
+        contract.yield_sources = yield_sources(vec![(60, 60), (40, 50)]);
+        contract.accounting.on_deposit(110);
+        contract.mint_tokens(sec(), 110);
+        assert_eq!(contract.get_max_withdrawal(), U128(60));
+
+        set_context(sec());
+        contract.withdraw(U128(70));
+    }
+
+    #[test]
+    fn test_rebalance_move(){
+        // the targets are 75 and 25
+        let sources = yield_sources(vec![(75, 40), (25, 60)]);
+        assert_eq!(sources.rebalance_move(), Some((1, 0, 35)));
+
+        let sources = yield_sources(vec![(75, 75), (25, 25)]);
+        assert_eq!(sources.rebalance_move(), None);
+
+        // less than 0.1% of the tokens
+        let sources = yield_sources(vec![(50, 50_004), (50, 49_996)]);
+        assert_eq!(sources.rebalance_move(), None);
+    }
+
+    #[test]
+    fn test_rebalance(){
+        let mut contract = get_contract();
+        set_context(mmmm());
+        contract.add_yield_source(source("other"), 100);
+        contract.yield_sources.sources[0].allocated = 100;
+
+        set_context(mmmm());
+        contract.rebalance();
+        assert_eq!(contract.yield_sources.sources[0].allocated, 50);
+        assert_eq!(contract.yield_sources.sources[1].allocated, 0);
+
+        set_promise_results(vec![PromiseResult::Successful(vec![])]);
+        contract.on_redeemed_for_rebalance(YieldSource::Burrow { address: burrow() }, source("other"), 50);
+        assert_eq!(contract.yield_sources.sources[1].allocated, 50);
+
+        // only 20 is used by the yield source
+        set_promise_results(vec![returned(20)]);
+        contract.on_deposited_for_rebalance(Some(YieldSource::Burrow { address: burrow() }), source("other"), 50);
+        assert_eq!(contract.yield_sources.sources[1].allocated, 20);
+        assert_eq!(contract.get_idle_balance(), U128(30));
+
+        // the idle tokens go first
+        set_context(mmmm());
+        contract.rebalance();
+        assert_eq!(contract.get_idle_balance(), U128(0));
+        assert_eq!(contract.yield_sources.sources[1].allocated, 50);
+    }
+
+    #[test]
+    fn test_rebalance_redeem_failed(){
+        let mut contract = get_contract();
+        set_context(mmmm());
+        contract.add_yield_source(source("other"), 100);
+        contract.yield_sources.sources[0].allocated = 100;
+
+        set_context(mmmm());
+        contract.rebalance();
+        set_promise_results(vec![PromiseResult::Failed]);
+        contract.on_redeemed_for_rebalance(YieldSource::Burrow { address: burrow() }, source("other"), 50);
+        assert_eq!(contract.yield_sources.sources[0].allocated, 100);
+        assert_eq!(contract.yield_sources.sources[1].allocated, 0);
+    }
+
+    #[test]
+    fn test_yield_sources_balance(){
+        let mut contract = get_contract();
+        contract.yield_sources.idle = 5;
+        contract.accounting.on_deposit(100);
+
+        let balance = |value: Balance| PromiseResult::Successful(near_sdk::serde_json::to_vec(&value).unwrap());
+        set_promise_results(vec![balance(70), balance(50)]);
+        assert_eq!(contract.get_yield_sources_balance_result(), Some(125));
+        assert_eq!(contract.on_get_pool_position(), vec![70, 50]);
+
+        set_promise_results(vec![balance(70), PromiseResult::Failed]);
+        assert_eq!(contract.get_yield_sources_balance_result(), None);
+    }
+
+    #[test]
+    fn test_idle_buffer(){
+        let mut contract = get_contract();
+        set_context(mmmm());
+        contract.set_idle_buffer_bps(1000);
+
+        // 10% of the principal stays in the pool
+        set_context(usdc());
+        contract.ft_on_transfer(sec(), U128(100), "".to_string());
+        assert_eq!(contract.get_idle_balance(), U128(10));
+        assert_eq!(contract.yield_sources.sources[0].allocated, 90);
+
+        // the idle tokens cover the withdrawal
+        set_context(sec());
+        contract.withdraw(U128(8));
+        assert_eq!(contract.get_idle_balance(), U128(2));
+        assert_eq!(contract.yield_sources.sources[0].allocated, 90);
+
+        set_context(sec());
+        contract.withdraw(U128(50));
+        assert_eq!(contract.get_idle_balance(), U128(2));
+        assert_eq!(contract.yield_sources.sources[0].allocated, 40);
+
+        // the target is 4
+        set_context(mmmm());
+        contract.rebalance();
+        assert_eq!(contract.yield_sources.sources[0].allocated, 38);
+        set_promise_results(vec![PromiseResult::Successful(vec![])]);
+        contract.on_redeemed_for_idle_buffer(YieldSource::Burrow { address: burrow() }, 2);
+        assert_eq!(contract.get_idle_balance(), U128(4));
+
+        // the buffer is drained to the yield source
+        set_context(mmmm());
+        contract.set_idle_buffer_bps(0);
+        contract.rebalance();
+        assert_eq!(contract.get_idle_balance(), U128(0));
+        assert_eq!(contract.yield_sources.sources[0].allocated, 42);
+    }
+
+    #[test]
+    fn test_idle_buffer_redeem_failed(){
+        let mut contract = get_contract();
+        contract.accounting.on_deposit(100);
+        contract.yield_sources.sources[0].allocated = 100;
+        set_context(mmmm());
+        contract.set_idle_buffer_bps(500);
+
+        contract.rebalance();
+        assert_eq!(contract.yield_sources.sources[0].allocated, 95);
+        set_promise_results(vec![PromiseResult::Failed]);
+        contract.on_redeemed_for_idle_buffer(YieldSource::Burrow { address: burrow() }, 5);
+        assert_eq!(contract.yield_sources.sources[0].allocated, 100);
+        assert_eq!(contract.get_idle_balance(), U128(0));
+    }
+
+    #[test]
+    fn test_rebalance_redeemed_amount(){
+        let mut contract = get_contract();
+        set_context(mmmm());
+        contract.add_yield_source(source("other"), 100);
+        contract.yield_sources.sources[0].allocated = 100;
+
+        // the unstake fee was lower than the covered fee
+        set_context(mmmm());
+        contract.rebalance();
+        set_promise_results(vec![returned(51)]);
+        contract.on_redeemed_for_rebalance(YieldSource::Burrow { address: burrow() }, source("other"), 50);
+        assert_eq!(contract.yield_sources.sources[1].allocated, 51);
+        // the redeemed value is taken out of the old yield source
+        assert_eq!(contract.yield_sources.sources[0].allocated, 49);
+
+        // the unstaked NEAR didn't cover the fee
+        contract.accounting.on_deposit(200);
+        set_context(mmmm());
+        contract.set_idle_buffer_bps(1000);
+        contract.rebalance();
+        set_promise_results(vec![returned(19)]);
+        contract.on_redeemed_for_idle_buffer(YieldSource::Burrow { address: burrow() }, 20);
+        assert_eq!(contract.get_idle_balance(), U128(19));
+    }
+
+    #[test]
+    #[should_panic(expected = "The yield source still has tokens allocated to it")]
+    fn test_remove_allocated_yield_source(){
+        let mut contract = get_contract();
+        contract.yield_sources.sources[0].allocated = 100;
+        set_context(mmmm());
+        contract.add_yield_source(source("other"), 100);
+        contract.remove_yield_source(YieldSource::Burrow { address: burrow() });
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller doesn't have the Keeper role")]
+    fn test_rebalance_not_keeper(){
+        let mut contract = get_contract();
+        set_context(sec());
+        contract.rebalance();
+    }
+}
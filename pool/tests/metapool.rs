@@ -0,0 +1,193 @@
+use anyhow::Ok;
+use near_sdk::{serde_json::json, json_types::U128, Balance, serde::{Serialize, Deserialize}};
+use workspaces::{Account, Contract, AccountId};
+mod utils;
+use crate::utils::to_yocto;
+
+const DEFAULT_GAS: u64 = 300_000_000_000_000;
+const DRAW_BYTES: &[u8] = include_bytes!("../../res/draw.wasm");
+const POOL_BYTES: &[u8] = include_bytes!("../../res/pool.wasm");
+const METAPOOL_BYTES: &[u8] = include_bytes!("../../res/metapool.wasm");
+const WNEAR_BYTES: &[u8] = include_bytes!("../../res/wnear.wasm");
+/// The liquid unstake fee of the mock Meta Pool, in basis points
+const LIQUID_UNSTAKE_FEE_BPS: u128 = 30;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GetAccountInfoResult{
+    account_id: near_sdk::AccountId,
+    st_near: U128,
+    valued_st_near: U128,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PoolAccountingView{
+    total_principal: U128,
+    total_yield_harvested: U128,
+    prizes_paid: U128,
+    rollover: U128,
+    reserve: U128,
+}
+
+async fn create_account(owner: &Account, acc_name: &str, balance: &str) -> anyhow::Result<Account>{
+    let acc = owner
+        .create_subaccount(acc_name)
+        .initial_balance(to_yocto(balance))
+        .transact()
+        .await?
+        .unwrap();
+
+    return Ok(acc);
+}
+
+async fn deploy(owner: &Account, acc_name: &str, bytes: &[u8]) -> anyhow::Result<Contract>{
+    let acc = create_account(owner, acc_name, "10").await?;
+    let contract = acc.deploy(bytes).await?.unwrap();
+
+    return Ok(contract);
+}
+
+async fn storage_deposit(caller: &Account, ft_contract: &AccountId) -> anyhow::Result<()>{
+    caller.call(ft_contract, "storage_deposit")
+        .args_json(json!({}))
+        .gas(DEFAULT_GAS)
+        .deposit(to_yocto("1"))
+        .transact()
+        .await?
+        .into_result()?;
+
+    return Ok(());
+}
+
+async fn ft_balance_of(caller: &Account, contract: &AccountId) -> anyhow::Result<u128>{
+    let balance = caller.call(contract, "ft_balance_of")
+        .args_json((caller.id(),))
+        .view()
+        .await?
+        .json::<U128>()?;
+
+    return Ok(balance.0);
+}
+
+/// Wraps `amount` NEAR and deposits the wNEAR in the pool
+async fn deposit(caller: &Account, pool: &AccountId, wnear: &AccountId, amount: Balance) -> anyhow::Result<()>{
+    caller.call(wnear, "near_deposit")
+        .gas(DEFAULT_GAS)
+        .deposit(amount)
+        .transact()
+        .await?
+        .into_result()?;
+
+    caller.call(wnear, "ft_transfer_call")
+        .args_json((pool, amount.to_string(), String::from(""), ""))
+        .max_gas()
+        .deposit(1)
+        .transact()
+        .await?
+        .into_result()?;
+
+    return Ok(());
+}
+
+async fn withdraw(caller: &Account, pool: &AccountId, amount: Balance) -> anyhow::Result<()>{
+    caller.call(pool, "withdraw")
+        .args_json(json!({"amount": amount.to_string()}))
+        .max_gas()
+        .deposit(1)
+        .transact()
+        .await?
+        .into_result()?;
+
+    return Ok(());
+}
+
+async fn get_st_near(metapool: &Contract, account_id: &AccountId) -> anyhow::Result<Balance>{
+    let account_info = metapool
+        .call("get_account_info")
+        .args_json(json!({"account_id": account_id}))
+        .view()
+        .await?
+        .json::<GetAccountInfoResult>()?;
+
+    return Ok(account_info.st_near.0);
+}
+
+async fn setup() -> anyhow::Result<(Contract, Contract, Contract, Account)>{
+    let workspaces = workspaces::sandbox().await?;
+    let root = workspaces.root_account().unwrap();
+
+    let wnear = deploy(&root, "wnear", WNEAR_BYTES).await?;
+    wnear.call("new").gas(DEFAULT_GAS).transact().await?.into_result()?;
+
+    let metapool = deploy(&root, "metapool", METAPOOL_BYTES).await?;
+    metapool.call("new").gas(DEFAULT_GAS).transact().await?.into_result()?;
+
+    let draw = deploy(&root, "draw", DRAW_BYTES).await?;
+    draw.call("new")
+        .args_json(json!({"owner_id": draw.id()}))
+        .gas(DEFAULT_GAS)
+        .transact()
+        .await?
+        .into_result()?;
+
+    let pool = deploy(&root, "pool", POOL_BYTES).await?;
+    pool.call("new_with_yield_source")
+        .args_json(json!({
+            "owner_id": pool.id(),
+            "token_for_deposit": wnear.id(),
+            "draw_contract": draw.id(),
+            "yield_source": {"Metapool": {"address": metapool.id()}}
+        }))
+        .gas(DEFAULT_GAS)
+        .transact()
+        .await?
+        .into_result()?;
+    storage_deposit(pool.as_account(), wnear.id()).await?;
+
+    return Ok((pool, metapool, wnear, root));
+}
+
+#[tokio::test]
+async fn test_metapool_withdraw_whole_position() -> anyhow::Result<()>{
+    let (pool, metapool, wnear, root) = setup().await?;
+    let test1 = create_account(&root, "test1", "20").await?;
+
+    deposit(&test1, pool.id(), wnear.id(), to_yocto("5")).await?;
+    assert_eq!(get_st_near(&metapool, pool.id()).await?, to_yocto("5"));
+    assert_eq!(ft_balance_of(&test1, pool.id()).await?, to_yocto("5"));
+
+    // all the stNEAR is burned, the fee is taken from the withdrawn NEAR
+    withdraw(&test1, pool.id(), to_yocto("5")).await?;
+    assert_eq!(get_st_near(&metapool, pool.id()).await?, 0);
+    assert_eq!(ft_balance_of(&test1, pool.id()).await?, 0);
+    let fee = to_yocto("5") * LIQUID_UNSTAKE_FEE_BPS / 10_000;
+    assert_eq!(ft_balance_of(&test1, wnear.id()).await?, to_yocto("5") - fee);
+
+    let accounting = pool.call("get_pool_accounting").view().await?.json::<PoolAccountingView>()?;
+    assert_eq!(accounting.total_principal.0, 0);
+
+    return Ok(());
+}
+
+#[tokio::test]
+async fn test_metapool_withdraw_covers_fee() -> anyhow::Result<()>{
+    let (pool, metapool, wnear, root) = setup().await?;
+    let test1 = create_account(&root, "test1", "20").await?;
+    let test2 = create_account(&root, "test2", "20").await?;
+
+    deposit(&test1, pool.id(), wnear.id(), to_yocto("5")).await?;
+    deposit(&test2, pool.id(), wnear.id(), to_yocto("5")).await?;
+
+    // stNEAR worth the amount with the highest covered fee is burned, the account gets the whole amount
+    withdraw(&test1, pool.id(), to_yocto("2")).await?;
+    assert_eq!(ft_balance_of(&test1, wnear.id()).await?, to_yocto("2"));
+    assert!(get_st_near(&metapool, pool.id()).await? < to_yocto("8"));
+
+    // the fee that was not taken stays in the pool
+    let idle = pool.call("get_idle_balance").view().await?.json::<U128>()?;
+    assert!(idle.0 > 0);
+    assert_eq!(ft_balance_of(pool.as_account(), wnear.id()).await?, idle.0);
+
+    return Ok(());
+}